@@ -0,0 +1,61 @@
+use std::{f64::consts::PI, io};
+
+use crate::{distribution::Distribution2D, image::Image, sampler, utils, vector3::Vector3};
+
+// Shape of the lens opening, which out of focus highlights take on
+pub enum Aperture {
+    Circular,
+    // Regular polygon formed by 'blades' straight diaphragm blades, turned by 'rotation' degrees
+    Polygonal { blades: u32, rotation: f64 },
+    // Opening given by the luminance of an image stretched over the lens, for custom bokeh
+    Image(Distribution2D),
+}
+
+impl Aperture {
+    pub fn image(image: &Image) -> Self {
+        let (width, height) = (image.width(), image.height());
+
+        let mut func = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                func.push(image.pixel(x, y).luminance());
+            }
+        }
+
+        Aperture::Image(Distribution2D::new(&func, width, height))
+    }
+
+    // Loads the aperture shape from a PPM image, white where the lens is open
+    pub fn load(path: &str) -> io::Result<Self> {
+        Ok(Aperture::image(&Image::load_ppm(path)?))
+    }
+
+    // Maps the sample 'u' to a point spread evenly over the opening, within [-1, 1] on both axes
+    pub fn sample(&self, u: (f64, f64)) -> Vector3 {
+        match self {
+            Aperture::Circular => sampler::concentric_disk(u),
+            Aperture::Polygonal { blades, rotation } => {
+                // Pick one of the polygon's equal triangles around the center, then a point in it,
+                // reusing what is left of the first value
+                let blades = (*blades).max(3) as f64;
+                let step = 2.0 * PI / blades;
+                let k = (u.0 * blades).floor();
+                let angle = utils::degrees_to_radians(*rotation) + k * step;
+                let a = Vector3::new(f64::cos(angle), f64::sin(angle), 0.0);
+                let b = Vector3::new(f64::cos(angle + step), f64::sin(angle + step), 0.0);
+
+                let mut s = u.0 * blades - k;
+                let mut t = u.1;
+                if s + t > 1.0 {
+                    (s, t) = (1.0 - s, 1.0 - t);
+                }
+                &(s * &a) + &(t * &b)
+            }
+            Aperture::Image(distribution) => {
+                let ((u, v), _) = distribution.sample_continuous(u.0, u.1);
+                // Image rows run from the top of the lens down
+                Vector3::new(2.0 * u - 1.0, 1.0 - 2.0 * v, 0.0)
+            }
+        }
+    }
+}
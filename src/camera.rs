@@ -1,22 +1,109 @@
-use std::{
-    fs::File,
-    io::{BufWriter, Write},
-};
+use std::f64::consts::PI;
 
 use crate::{
+    aperture::Aperture,
     color::Color,
+    filter::{Filter, FilterSampler},
     hittable::{HitRecord, Hittable},
+    image::Image,
     interval::Interval,
+    lens_system::LensSystem,
+    pdf::{self, Pdf},
     ray::Ray,
-    utils,
+    sampler::{Sampler, Sampling},
+    scene::Scene,
+    spectrum, utils,
     vector3::{Point3, Vector3},
 };
 
+// How camera rays leave the 'lookfrom', 'lookat', 'vup' frame
+#[derive(Clone, Copy)]
+pub enum Projection {
+    // Pinhole or thin lens, with 'vfov', 'defocus_angle' and 'focus_dist'
+    Perspective,
+    // Parallel rays along the view direction, through a viewport 'height' units tall
+    Orthographic { height: f64 },
+    // Equidistant fisheye: the angle off the view direction grows linearly with the distance
+    // from the image center, reaching half of 'fov' degrees at the left and right edges. The
+    // image circle touches those edges, and the corners outside it stay black
+    Fisheye { fov: f64 },
+    // Full 360x180 degree panorama in longitude and latitude, best with a 2:1 aspect ratio
+    Equirectangular,
+}
+
+#[derive(Clone, Copy)]
+pub enum StereoLayout {
+    // Left eye on the left half of output.ppm, right eye on the right half
+    SideBySide,
+    // Left eye on the top half of output.ppm, right eye on the bottom half
+    OverUnder,
+    // output_left.ppm and output_right.ppm
+    Separate,
+}
+
+#[derive(Clone, Copy)]
+pub struct Stereo {
+    pub interpupillary_distance: f64,
+    // Distance at which the two views line up, so objects there appear at screen depth
+    pub convergence: f64,
+    pub layout: StereoLayout,
+}
+
+// Thin lens described like a real camera, with lengths in millimeters and scene units in meters
+#[derive(Clone, Copy)]
+pub struct ThinLens {
+    pub f_number: f64,
+    pub focal_length: f64,
+    pub sensor_width: f64,
+}
+
+// Movements of a view camera's lens relative to the film
+#[derive(Clone, Copy, Default)]
+pub struct TiltShift {
+    // Lens shift right and up, as fractions of the viewport width and height. Shifting instead
+    // of turning the camera keeps vertical lines parallel
+    pub shift_horizontal: f64,
+    pub shift_vertical: f64,
+    // Rotation of the plane of focus in degrees, following Scheimpflug's rule: tilt turns its
+    // top away from the camera and swing turns its right side away
+    pub tilt: f64,
+    pub swing: f64,
+}
+
+// Spends more samples on pixels that are still noisy, and fewer on smooth ones like the sky
+#[derive(Clone, Copy)]
+pub struct AdaptiveSampling {
+    pub min_samples: i32,
+    pub max_samples: i32,
+    // A pixel stops once the 95% confidence interval of its luminance is narrower than this
+    // fraction of its mean on either side
+    pub threshold: f64,
+    // Also writes how many samples each pixel took to output_samples.ppm, from blue at
+    // 'min_samples' to red at 'max_samples'
+    pub heatmap: bool,
+}
+
+// Where auto-focus measures the distance to the scene
+#[derive(Clone, Copy)]
+pub enum FocusTarget {
+    // Whatever lies in the direction of 'lookat'
+    Lookat,
+    // Whatever is seen at the center of the pixel at column i, row j
+    Pixel(i32, i32),
+}
+
 pub struct Camera {
     pub aspect_ratio: f64,
     pub image_width: i32,
     pub samples_per_pixel: i32,
+    // How the samples of a pixel are spread over the pixel, the lens and each bounce
+    pub sampling: Sampling,
+    // Varies the samples taken per pixel instead of 'samples_per_pixel'
+    pub adaptive: Option<AdaptiveSampling>,
+    // Weights the samples by their position in and around the pixel
+    pub filter: Filter,
     pub max_depth: i32,
+    pub russian_roulette_depth: i32,
 
     pub vfov: f64,
     pub lookfrom: Point3,
@@ -25,9 +112,23 @@ pub struct Camera {
 
     pub defocus_angle: f64,
     pub focus_dist: f64,
+    pub tilt_shift: TiltShift,
+    pub aperture: Aperture,
+    // Optical vignetting from 0 to 1: how far off-axis points see the lens clipped into a
+    // cat's eye shape, reaching a half-covered lens in the corners at 1
+    pub cat_eye: f64,
+    // Sets the field of view and defocus from the lens instead of 'vfov' and 'defocus_angle'
+    pub lens: Option<ThinLens>,
+    // Traces rays through real lens elements instead, with 'lookfrom' at the film. Only the
+    // perspective projection can be rendered through one
+    pub lens_system: Option<LensSystem>,
+
+    pub spectral: bool,
+
+    pub projection: Projection,
+    pub stereo: Option<Stereo>,
 
     image_height: i32,
-    pixel_sample_scale: f64,
     center: Point3,
     pixel00_loc: Point3,
     pixel_delta_u: Vector3,
@@ -37,6 +138,10 @@ pub struct Camera {
     w: Vector3,
     defocus_disk_u: Vector3,
     defocus_disk_v: Vector3,
+    defocus_radius: f64,
+    focus_normal: Vector3,
+    spectral_white: Color,
+    filter_sampler: FilterSampler,
 }
 
 impl Camera {
@@ -45,7 +150,11 @@ impl Camera {
             aspect_ratio: 1.0,
             image_width: 100,
             samples_per_pixel: 10,
+            sampling: Sampling::Independent,
+            adaptive: None,
+            filter: Filter::Box { radius: 0.5 },
             max_depth: 10,
+            russian_roulette_depth: 3,
 
             vfov: 90.0,
             lookfrom: Point3::zero(),
@@ -54,9 +163,18 @@ impl Camera {
 
             defocus_angle: 0.0,
             focus_dist: 10.0,
+            tilt_shift: TiltShift::default(),
+            aperture: Aperture::Circular,
+            cat_eye: 0.0,
+            lens: None,
+            lens_system: None,
+
+            spectral: false,
+
+            projection: Projection::Perspective,
+            stereo: None,
 
             image_height: 0,
-            pixel_sample_scale: 0.0,
             center: Point3::zero(),
             pixel00_loc: Point3::zero(),
             pixel_delta_u: Vector3::zero(),
@@ -66,134 +184,765 @@ impl Camera {
             w: Vector3::zero(),
             defocus_disk_u: Vector3::zero(),
             defocus_disk_v: Vector3::zero(),
+            defocus_radius: 0.0,
+            focus_normal: Vector3::zero(),
+            spectral_white: Color::zero(),
+            filter_sampler: FilterSampler::new(Filter::Box { radius: 0.5 }),
         }
     }
 
     fn initialize(&mut self) {
         self.image_height = (self.image_width as f64 / self.aspect_ratio) as i32;
         self.image_height = self.image_height.max(1);
-
-        self.pixel_sample_scale = 1.0 / self.samples_per_pixel as f64;
+        self.filter_sampler = FilterSampler::new(self.filter);
 
         self.center = self.lookfrom;
 
+        let (vfov, defocus_angle) = self.view_angles();
+
         // Determime viewport dimensions
 
-        let theta = utils::degrees_to_radians(self.vfov);
-        let h = f64::tan(theta / 2.0);
-        let viewport_height = 2.0 * h * self.focus_dist;
+        let viewport_height = match self.projection {
+            Projection::Orthographic { height } => height,
+            _ => {
+                let theta = utils::degrees_to_radians(vfov);
+                let h = f64::tan(theta / 2.0);
+                2.0 * h * self.focus_dist
+            }
+        };
         let viewport_width = viewport_height * (self.image_width as f64 / self.image_height as f64);
 
         // Calculate the u, v, w unit basis vectors for the camera coordinate frame
         self.w = Vector3::unit_vector(&(&self.lookfrom - &self.lookat));
-        self.u = Vector3::unit_vector(&&Vector3::cross(&self.vup, &self.w));
+        self.u = Vector3::unit_vector(&Vector3::cross(&self.vup, &self.w));
         self.v = Vector3::cross(&self.w, &self.u);
 
         // Calculate the vectors across the horizontal and down the vertical viewport edges
-        let viewport_u = viewport_width as f64 * &self.u;
-        let viewport_v = viewport_height as f64 * &-self.v;
+        let viewport_u = viewport_width * &self.u;
+        let viewport_v = viewport_height * &-self.v;
 
         // Calculate the horizontal and vertical delta vectors from pixel to pixel
         self.pixel_delta_u = &viewport_u / self.image_width as f64;
         self.pixel_delta_v = &viewport_v / self.image_height as f64;
 
-        // Calculate the location of the upper left pixel.
-        let viewport_upper_left = &(&(&self.center - &(self.focus_dist as f64 * &self.w))
+        // Calculate the location of the upper left pixel, moving the viewport with the lens shift
+        let shift = &(self.tilt_shift.shift_horizontal * &viewport_u)
+            - &(self.tilt_shift.shift_vertical * &viewport_v);
+        let viewport_upper_left = &(&(&(&self.center - &(self.focus_dist * &self.w))
             - &(&viewport_u / 2.0))
-            - &(&viewport_v / 2.0);
+            - &(&viewport_v / 2.0))
+            + &shift;
 
         // Calculate the camera defocus disk basis vectors
-        let defocus_radius =
-            self.focus_dist * f64::tan(utils::degrees_to_radians(self.defocus_angle / 2.0));
-        self.defocus_disk_u = &self.u * defocus_radius;
-        self.defocus_disk_v = &self.v * defocus_radius;
+        self.defocus_radius =
+            self.focus_dist * f64::tan(utils::degrees_to_radians(defocus_angle.max(0.0) / 2.0));
+        self.defocus_disk_u = &self.u * self.defocus_radius;
+        self.defocus_disk_v = &self.v * self.defocus_radius;
+
+        // Rays from the defocus disk converge on the plane of focus, which tilt and swing turn
+        // away from the viewport
+        let tilt = utils::degrees_to_radians(self.tilt_shift.tilt);
+        let swing = utils::degrees_to_radians(self.tilt_shift.swing);
+        self.focus_normal = Vector3::unit_vector(
+            &(&(&(f64::cos(tilt) * f64::cos(swing) * &self.w) + &(f64::sin(tilt) * &self.v))
+                + &(f64::cos(tilt) * f64::sin(swing) * &self.u)),
+        );
 
         self.pixel00_loc =
             &viewport_upper_left + &(0.5 * &(&self.pixel_delta_u + &self.pixel_delta_v));
+
+        if let Some(lens_system) = &mut self.lens_system {
+            let aspect_ratio = self.image_width as f64 / self.image_height as f64;
+            lens_system.prepare(1000.0 * self.focus_dist, aspect_ratio);
+        }
+
+        self.spectral_white = spectrum::flat_spectrum_rgb();
     }
 
-    pub fn render<T: Hittable>(&mut self, world: &T) -> std::io::Result<()> {
+    pub fn focus_on(&mut self, scene: &Scene, target: FocusTarget) -> Option<f64> {
+        // Sets 'focus_dist' to the distance of the first surface along a probe ray towards
+        // 'target', measured along the view direction, and returns it. Pixels are probed as a
+        // perspective camera sees them. 'focus_dist' stays unchanged when the probe hits nothing
         self.initialize();
 
-        // File
-        let file = File::create("output.ppm")?;
-        let mut writer = BufWriter::new(file);
+        let direction = match target {
+            FocusTarget::Lookat => &self.lookat - &self.lookfrom,
+            FocusTarget::Pixel(i, j) => {
+                let pixel_center = &(&self.pixel00_loc + &(i as f64 * &self.pixel_delta_u))
+                    + &(j as f64 * &self.pixel_delta_v);
+                &pixel_center - &self.center
+            }
+        };
+
+        let mut rec = HitRecord::zero();
+        if !scene.world.hit(
+            &Ray::new(&self.center, &direction),
+            Interval::new(0.001, f64::INFINITY),
+            &mut rec,
+        ) {
+            return None;
+        }
 
-        // Render
-        writeln!(
-            writer,
-            "P3\n{} {}\n255",
-            self.image_width, self.image_height
-        )?;
+        self.focus_dist = Vector3::dot(&(&rec.p - &self.center), &-self.w);
+        Some(self.focus_dist)
+    }
+
+    pub fn defocus_angle_for_f_number(&self, f_number: f64, sensor_width: f64) -> f64 {
+        // Returns the defocus angle of a lens stopped down to 'f_number', whose focal length
+        // gives 'vfov' on a sensor 'sensor_width' millimeters wide, 36 for full frame, taking
+        // scene units as meters
+        let sensor_height = sensor_width / self.aspect_ratio;
+        let focal_length =
+            sensor_height / (2.0 * f64::tan(utils::degrees_to_radians(self.vfov) / 2.0));
+
+        self.aperture_defocus_angle(focal_length, f_number)
+    }
+
+    pub fn depth_of_field(&self, circle_of_confusion: f64) -> (f64, f64) {
+        // Returns the nearest and farthest distances whose blur stays within
+        // 'circle_of_confusion' pixels across. The far limit is infinite past the hyperfocal
+        // distance
+        let (vfov, defocus_angle) = self.view_angles();
+        let viewport_height =
+            2.0 * f64::tan(utils::degrees_to_radians(vfov) / 2.0) * self.focus_dist;
+        let pixel_size = viewport_height * self.aspect_ratio / self.image_width as f64;
+
+        // A point at distance d is blurred over aperture * |d - focus_dist| / d on the
+        // viewport, which sits at the focus distance
+        let c = circle_of_confusion * pixel_size;
+        let aperture =
+            2.0 * self.focus_dist * f64::tan(utils::degrees_to_radians(defocus_angle) / 2.0);
+        if aperture <= 0.0 {
+            return (0.0, f64::INFINITY);
+        }
+
+        let near = aperture * self.focus_dist / (aperture + c);
+        let far = if aperture > c {
+            aperture * self.focus_dist / (aperture - c)
+        } else {
+            f64::INFINITY
+        };
+
+        (near, far)
+    }
+
+    fn view_angles(&self) -> (f64, f64) {
+        // Returns the vertical field of view and defocus angle in effect, in degrees
+        match self.lens {
+            Some(lens) => self.lens_angles(&lens),
+            None => (self.vfov, self.defocus_angle),
+        }
+    }
+
+    fn aperture_defocus_angle(&self, focal_length: f64, f_number: f64) -> f64 {
+        // Returns the defocus angle, in degrees, of a lens with 'focal_length' millimeters
+        // stopped down to 'f_number'
+        let aperture_radius = focal_length / (2.0 * f_number) / 1000.0;
+        (2.0 * f64::atan(aperture_radius / self.focus_dist)).to_degrees()
+    }
+
+    fn lens_angles(&self, lens: &ThinLens) -> (f64, f64) {
+        // Returns the vertical field of view and defocus angle, in degrees, of 'lens'
+
+        // Focusing moves the lens away from the sensor, which narrows the field of view
+        let focus_dist = 1000.0 * self.focus_dist;
+        let image_distance = if focus_dist > lens.focal_length {
+            lens.focal_length * focus_dist / (focus_dist - lens.focal_length)
+        } else {
+            lens.focal_length
+        };
+        let sensor_height = lens.sensor_width / self.aspect_ratio;
+        let vfov = 2.0 * f64::atan(sensor_height / (2.0 * image_distance));
+
+        (
+            vfov.to_degrees(),
+            self.aperture_defocus_angle(lens.focal_length, lens.f_number),
+        )
+    }
+
+    pub fn render(&mut self, scene: &Scene) -> std::io::Result<()> {
+        if self.lens_system.is_some() && !matches!(self.projection, Projection::Perspective) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "lens systems only support the perspective projection",
+            ));
+        }
+        self.initialize();
+
+        let heatmap = self.adaptive.is_some_and(|adaptive| adaptive.heatmap);
+
+        let Some(stereo) = self.stereo else {
+            let (image, samples) = self.render_eye(0.0, scene);
+            image.write_ppm("output.ppm")?;
+            if heatmap {
+                samples.write_ppm("output_samples.ppm")?;
+            }
+            println!("Done c:\n");
+            return Ok(());
+        };
+
+        // Eyes sit half the interpupillary distance to either side of the camera
+        let half_distance = stereo.interpupillary_distance / 2.0;
+        let (left, left_samples) = self.render_eye(-half_distance, scene);
+        let (right, right_samples) = self.render_eye(half_distance, scene);
+
+        Camera::write_stereo(&left, &right, stereo.layout, "output")?;
+        if heatmap {
+            Camera::write_stereo(
+                &left_samples,
+                &right_samples,
+                stereo.layout,
+                "output_samples",
+            )?;
+        }
+        println!("Done c:\n");
+        Ok(())
+    }
+
+    fn write_stereo(
+        left: &Image,
+        right: &Image,
+        layout: StereoLayout,
+        name: &str,
+    ) -> std::io::Result<()> {
+        match layout {
+            StereoLayout::SideBySide => left.side_by_side(right).write_ppm(&format!("{name}.ppm")),
+            StereoLayout::OverUnder => left.over_under(right).write_ppm(&format!("{name}.ppm")),
+            StereoLayout::Separate => {
+                left.write_ppm(&format!("{name}_left.ppm"))?;
+                right.write_ppm(&format!("{name}_right.ppm"))
+            }
+        }
+    }
+
+    fn render_eye(&self, eye: f64, scene: &Scene) -> (Image, Image) {
+        // Renders the view from 'eye' units to the right of the camera, along with a heatmap of
+        // the samples each pixel took
+        let size = (self.image_width * self.image_height) as usize;
+        let mut pixels = Vec::with_capacity(size);
+        let mut heat = Vec::with_capacity(size);
+
+        let (min_samples, max_samples) = match self.adaptive {
+            Some(adaptive) => (adaptive.min_samples.max(2), adaptive.max_samples),
+            None => (self.samples_per_pixel, self.samples_per_pixel),
+        };
+        let max_samples = max_samples.max(min_samples);
+        let mut sampler =
+            self.sampling
+                .sampler(max_samples as u32, self.image_width, self.image_height);
 
         for j in 0..self.image_height {
             println!("Scanlines remaining: {}", self.image_height - j);
             for i in 0..self.image_width {
-                let mut pixel_color = Color::new(0.0, 0.0, 0.0);
-
-                for _ in 0..self.samples_per_pixel {
-                    let ray = self.get_ray(i, j);
-                    pixel_color += &Camera::ray_color(&ray, self.max_depth, world);
+                let mut statistics = PixelStatistics::new();
+
+                for index in 0..max_samples {
+                    sampler.start_pixel_sample(i, j, index as u32);
+                    // The filter picks where in and around the pixel the sample lands
+                    let (dx, dy, weight) = self.filter_sampler.sample(sampler.get_2d());
+                    let (x, y) = (i as f64 + 0.5 + dx, j as f64 + 0.5 + dy);
+                    let color = self.sample_color(x, y, eye, scene, sampler.as_mut());
+                    statistics.add(&color, weight);
+
+                    if let Some(adaptive) = self.adaptive {
+                        if index + 1 >= min_samples && statistics.converged(adaptive.threshold) {
+                            break;
+                        }
+                    }
                 }
-                Color::write_color(&mut writer, &(self.pixel_sample_scale * &pixel_color))?;
+
+                pixels.push(statistics.color());
+                heat.push(heat_color(
+                    (statistics.count - min_samples) as f64
+                        / (max_samples - min_samples).max(1) as f64,
+                ));
             }
         }
-        println!("Done c:\n");
-        Ok(())
+
+        let width = self.image_width as usize;
+        let height = self.image_height as usize;
+        (
+            Image::new(width, height, pixels),
+            Image::new(width, height, heat),
+        )
     }
 
-    fn get_ray(&self, i: i32, j: i32) -> Ray {
-        // Construct a camera ray originating from the defocus disk and directed at a randomly samples
-        // point around the pixel location i, j
+    fn sample_color(
+        &self,
+        x: f64,
+        y: f64,
+        eye: f64,
+        scene: &Scene,
+        sampler: &mut dyn Sampler,
+    ) -> Color {
+        // Parts of the image outside a fisheye's field of view, or blocked by the lens, stay black
+        let Some((ray, weight)) = self.camera_ray(x, y, eye, sampler) else {
+            return Color::zero();
+        };
+        if !self.spectral {
+            return weight * &self.ray_color(&ray, scene, sampler);
+        }
 
-        let offset = Camera::sample_square();
-        let pixel_sample = &(&self.pixel00_loc + &((i as f64 + offset.x) * &self.pixel_delta_u))
-            + &((j as f64 + offset.y) * &self.pixel_delta_v);
+        // Trace a single wavelength and project it back to RGB through CIE XYZ
+        let lambda = spectrum::sample_wavelength(sampler.get_1d());
+        let radiance = self.ray_color(&ray.with_wavelength(Some(lambda)), scene, sampler);
+        weight
+            * &spectrum::sample_to_rgb(
+                radiance.x,
+                lambda,
+                spectrum::wavelength_pdf(),
+                &self.spectral_white,
+            )
+    }
 
-        let ray_origin = if self.defocus_angle <= 0.0 {
-            self.center
-        } else {
-            self.defocus_disk_sample()
+    fn camera_ray(
+        &self,
+        x: f64,
+        y: f64,
+        eye: f64,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Ray, f64)> {
+        // Returns a ray for the film position x, y, in pixels from the top left corner of the
+        // image, and the weight of its contribution, which only lens systems change
+        let Some(lens_system) = &self.lens_system else {
+            return self.get_ray(x, y, eye, sampler).map(|ray| (ray, 1.0));
         };
-        let ray_direction = &pixel_sample - &ray_origin;
 
-        Ray::new(&ray_origin, &ray_direction)
+        let s = x / self.image_width as f64;
+        let t = y / self.image_height as f64;
+        let (ray, weight) = lens_system.generate_ray(s, t, sampler.get_2d())?;
+
+        // Lens space shares the camera's axes and is measured in millimeters
+        let origin = ray.origin();
+        let direction = ray.direction();
+        let ray_origin = &(&self.center + &(eye * &self.u))
+            + &(&self.camera_direction(origin.x, origin.y, -origin.z) / 1000.0);
+        let ray_direction = self.camera_direction(direction.x, direction.y, -direction.z);
+
+        Some((Ray::new(&ray_origin, &ray_direction), weight))
     }
 
-    fn defocus_disk_sample(&self) -> Point3 {
-        let p = Vector3::random_in_unit_disk();
-        &(&self.center + &(p.x * &self.defocus_disk_u)) + &(p.y * &self.defocus_disk_v)
+    fn get_ray(&self, x: f64, y: f64, eye: f64, sampler: &mut dyn Sampler) -> Option<Ray> {
+        // Construct a camera ray through the film position x, y, in pixels from the top left
+        // corner of the image, as seen from 'eye' units to the right of the camera
+
+        // 'pixel00_loc' is the center of the top left pixel
+        let pixel_sample = &(&self.pixel00_loc + &((x - 0.5) * &self.pixel_delta_u))
+            + &((y - 0.5) * &self.pixel_delta_v);
+        let eye_center = &self.center + &(eye * &self.u);
+
+        // Position on the image, from 0 to 1 left to right and top to bottom
+        let s = x / self.image_width as f64;
+        let t = y / self.image_height as f64;
+
+        match self.projection {
+            Projection::Perspective => {
+                // Each eye looks through its own off-axis viewport, shifted so that both views
+                // meet on the convergence plane
+                let shift = match self.stereo {
+                    Some(stereo) => eye * (1.0 - self.focus_dist / stereo.convergence),
+                    None => 0.0,
+                };
+                let pixel_sample = &pixel_sample + &(shift * &self.u);
+
+                // Rays originate from the defocus disk
+                if self.defocus_radius <= 0.0 {
+                    return Some(Ray::new(&eye_center, &(&pixel_sample - &eye_center)));
+                }
+                let ray_origin = &eye_center + &self.defocus_disk_sample(s, t, sampler.get_2d())?;
+
+                // and pass through where the pinhole ray meets the plane of focus. Parts of a
+                // tilted plane that lie behind the camera are focused at infinity
+                let pinhole = &pixel_sample - &eye_center;
+                let focus_center = &self.center - &(self.focus_dist * &self.w);
+                let distance = Vector3::dot(&self.focus_normal, &(&focus_center - &eye_center))
+                    / Vector3::dot(&self.focus_normal, &pinhole);
+                let ray_direction = if distance > 0.0 {
+                    &(&eye_center + &(distance * &pinhole)) - &ray_origin
+                } else {
+                    pinhole
+                };
+
+                Some(Ray::new(&ray_origin, &ray_direction))
+            }
+            Projection::Orthographic { .. } => {
+                // Bring the viewport sample back to the camera plane
+                let ray_origin = &(&pixel_sample + &(self.focus_dist * &self.w)) + &(eye * &self.u);
+                Some(Ray::new(&ray_origin, &-self.w))
+            }
+            Projection::Fisheye { fov } => {
+                let x = 2.0 * s - 1.0;
+                let y = (1.0 - 2.0 * t) * self.image_height as f64 / self.image_width as f64;
+                // Outside the image circle is beyond the field of view. Fields of view wider
+                // than 360 degrees also run past straight behind the camera inside it
+                let r = f64::sqrt(x * x + y * y);
+                let theta = r * utils::degrees_to_radians(fov) / 2.0;
+                if r > 1.0 || theta > PI {
+                    return None;
+                }
+                let phi = f64::atan2(y, x);
+
+                let direction = self.camera_direction(
+                    f64::sin(theta) * f64::cos(phi),
+                    f64::sin(theta) * f64::sin(phi),
+                    f64::cos(theta),
+                );
+                Some(self.converge(&eye_center, &direction))
+            }
+            Projection::Equirectangular => {
+                let longitude = (2.0 * s - 1.0) * PI;
+                let latitude = (0.5 - t) * PI;
+
+                let direction = self.camera_direction(
+                    f64::cos(latitude) * f64::sin(longitude),
+                    f64::sin(latitude),
+                    f64::cos(latitude) * f64::cos(longitude),
+                );
+
+                // Omni-directional stereo: the eyes turn with the view, so their baseline stays
+                // perpendicular to each longitude
+                let baseline =
+                    self.camera_direction(f64::cos(longitude), 0.0, -f64::sin(longitude));
+                let eye_center = &self.center + &(eye * &baseline);
+                Some(self.converge(&eye_center, &direction))
+            }
+        }
     }
 
-    fn sample_square() -> Vector3 {
-        Vector3::new(
-            utils::canonical_random_number() - 0.5,
-            utils::canonical_random_number() - 0.5,
-            0.0,
-        )
+    fn converge(&self, eye_center: &Point3, direction: &Vector3) -> Ray {
+        // Aims a ray from 'eye_center' at the point the camera sees along 'direction' at the
+        // convergence distance
+        match self.stereo {
+            Some(stereo) => {
+                let target =
+                    &self.center + &(stereo.convergence * &Vector3::unit_vector(direction));
+                Ray::new(eye_center, &(&target - eye_center))
+            }
+            None => Ray::new(eye_center, direction),
+        }
     }
 
-    fn ray_color<T: Hittable>(ray: &Ray, depth: i32, world: &T) -> Color {
-        // If we've exceeded the ray bounce, no more lights is gathered
-        if depth <= 0 {
-            return Color::new(0.0, 0.0, 0.0);
+    fn camera_direction(&self, right: f64, up: f64, forward: f64) -> Vector3 {
+        // Direction given by its components along the camera's right, up and viewing directions
+        &(&(right * &self.u) + &(up * &self.v)) - &(forward * &self.w)
+    }
+
+    fn defocus_disk_sample(&self, s: f64, t: f64, u: (f64, f64)) -> Option<Vector3> {
+        // Offset from the center of the lens given by the sample 'u', for a ray towards the
+        // image position 's', 't'
+        let p = self.aperture.sample(u);
+
+        // Rays reaching off-axis points also have to pass the front of the lens barrel, which
+        // appears shifted towards the image position and blocks part of the opening
+        if self.cat_eye > 0.0 {
+            let aspect = self.image_height as f64 / self.image_width as f64;
+            let (x, y) = (2.0 * s - 1.0, (1.0 - 2.0 * t) * aspect);
+            let shift = self.cat_eye / f64::sqrt(1.0 + aspect * aspect);
+            let clipped = Vector3::new(p.x - shift * x, p.y - shift * y, 0.0);
+            if clipped.length_squared() > 1.0 {
+                return None;
+            }
         }
 
-        let mut rec = HitRecord::zero();
+        Some(&(p.x * &self.defocus_disk_u) + &(p.y * &self.defocus_disk_v))
+    }
+
+    fn ray_color(&self, ray: &Ray, scene: &Scene, sampler: &mut dyn Sampler) -> Color {
+        // Follow the path one bounce at a time, carrying the product of the BSDF weights
+        // gathered so far in 'throughput'
+        let mut radiance = Color::zero();
+        let mut throughput = Color::new(1.0, 1.0, 1.0);
+        let mut ray = *ray;
+        let wavelength = ray.wavelength();
+        // Density of the BSDF sample that produced 'ray', if it was not specular
+        let mut bsdf_pdf = None;
+
+        for depth in 0..self.max_depth {
+            let mut rec = HitRecord::zero();
+            let samples = BounceSamples::draw(sampler, scene.lights.len());
+
+            ray = ray.with_alpha_sample(samples.alpha);
+            if !scene
+                .world
+                .hit(&ray, Interval::new(0.001, f64::INFINITY), &mut rec)
+            {
+                // Escaped rays that the environment could also have sampled directly share
+                // the contribution with 'direct_lighting' through multiple importance sampling
+                let mut background = spectrum::at_wavelength(
+                    &scene.environment.radiance(&ray.direction()),
+                    wavelength,
+                );
+                if let Some(bsdf_pdf) = bsdf_pdf {
+                    let env_pdf = scene.environment.pdf(&ray.direction());
+                    background *= pdf::power_heuristic(bsdf_pdf, env_pdf);
+                }
+
+                radiance += &(&throughput * &background);
+                break;
+            }
+
+            let Some(srec) = rec
+                .mat
+                .scatter(&ray, &rec, samples.scatter.0, samples.scatter.1)
+            else {
+                break;
+            };
+
+            if srec.is_specular {
+                throughput = &throughput * &spectrum::at_wavelength(&srec.attenuation, wavelength);
+                ray = srec.specular_ray.with_wavelength(wavelength);
+                bsdf_pdf = None;
+            } else {
+                let Some(pdf) = srec.pdf else {
+                    break;
+                };
+
+                let direct = Camera::direct_lighting(&ray, &rec, pdf.as_ref(), scene, &samples);
+                radiance += &(&throughput * &direct);
+
+                let direction = pdf.generate(samples.generate.0, samples.generate.1);
+                if direction.near_zero() {
+                    break;
+                }
+
+                let scattered = Ray::new(&rec.p, &direction).with_wavelength(wavelength);
+                let pdf_value = pdf.value(&scattered.direction());
+                if pdf_value <= 0.0 {
+                    break;
+                }
+
+                let bsdf =
+                    spectrum::at_wavelength(&rec.mat.eval(&ray, &rec, &scattered), wavelength);
+                throughput = &(&throughput * &bsdf) / pdf_value;
+                ray = scattered;
+                bsdf_pdf = Some(pdf_value);
+            }
+
+            // Russian roulette: past the minimum depth, end dim paths at random and boost the
+            // survivors so the estimate stays unbiased
+            if depth + 1 >= self.russian_roulette_depth {
+                let survival = f64::min(throughput.x.max(throughput.y).max(throughput.z), 1.0);
+                if survival <= 0.0 || samples.roulette >= survival {
+                    break;
+                }
+                throughput /= survival;
+            }
+        }
+
+        radiance
+    }
+
+    fn direct_lighting(
+        ray: &Ray,
+        rec: &HitRecord,
+        bsdf_pdf: &dyn Pdf,
+        scene: &Scene,
+        samples: &BounceSamples,
+    ) -> Color {
+        // Gather light from the analytic lights, which rays can never hit, through shadow rays
+        let mut direct = Color::zero();
+        let wavelength = ray.wavelength();
+
+        for (light, &(u, alpha)) in scene.lights.iter().zip(&samples.lights) {
+            let Some(sample) = light.sample(&rec.p, u) else {
+                continue;
+            };
+
+            let shadow_ray = Ray::new(&rec.p, &sample.direction)
+                .with_wavelength(wavelength)
+                .with_alpha_sample(alpha);
+            let bsdf = spectrum::at_wavelength(&rec.mat.eval(ray, rec, &shadow_ray), wavelength);
+            if bsdf.near_zero() {
+                continue;
+            }
 
-        if world.hit(ray, Interval::new(0.001, f64::INFINITY), &mut rec) {
-            let mut scattered = Ray::zero();
-            let mut attenuation = Color::zero();
-            let material = &rec.mat;
+            let mut shadow_rec = HitRecord::zero();
+            if scene.world.hit(
+                &shadow_ray,
+                Interval::new(0.001, sample.distance - 0.001),
+                &mut shadow_rec,
+            ) {
+                continue;
+            }
+
+            direct += &(&bsdf * &spectrum::at_wavelength(&sample.radiance, wavelength));
+        }
 
-            if material.scatter(ray, &rec, &mut attenuation, &mut scattered) {
-                return &attenuation * &Camera::ray_color(&scattered, depth - 1, world);
+        // Sample the environment as a light, weighted against BSDF sampling
+        let (u, alpha) = samples.environment;
+        if let Some(sample) = scene.environment.sample(u) {
+            let shadow_ray = Ray::new(&rec.p, &sample.direction)
+                .with_wavelength(wavelength)
+                .with_alpha_sample(alpha);
+            let bsdf = spectrum::at_wavelength(&rec.mat.eval(ray, rec, &shadow_ray), wavelength);
+
+            let mut shadow_rec = HitRecord::zero();
+            if !bsdf.near_zero()
+                && !scene.world.hit(
+                    &shadow_ray,
+                    Interval::new(0.001, f64::INFINITY),
+                    &mut shadow_rec,
+                )
+            {
+                let weight = pdf::power_heuristic(sample.pdf, bsdf_pdf.value(&sample.direction));
+                let radiance = spectrum::at_wavelength(&sample.radiance, wavelength);
+                direct += &((weight / sample.pdf) * &(&bsdf * &radiance));
             }
+        }
+
+        direct
+    }
+}
+
+// Sample values for one bounce of a path. They are all drawn before the bounce, whatever it turns
+// out to need, so that a dimension of the sampler always feeds the same decision
+struct BounceSamples {
+    alpha: f64,
+    scatter: (f64, (f64, f64)),
+    // Point on each light, then the alpha sample of its shadow ray
+    lights: Vec<((f64, f64), f64)>,
+    environment: ((f64, f64), f64),
+    generate: (f64, (f64, f64)),
+    roulette: f64,
+}
+
+impl BounceSamples {
+    fn draw(sampler: &mut dyn Sampler, light_count: usize) -> Self {
+        let alpha = sampler.get_1d();
+        let scatter = (sampler.get_1d(), sampler.get_2d());
+        let lights = (0..light_count)
+            .map(|_| (sampler.get_2d(), sampler.get_1d()))
+            .collect();
+        let environment = (sampler.get_2d(), sampler.get_1d());
+        let generate = (sampler.get_1d(), sampler.get_2d());
+
+        Self {
+            alpha,
+            scatter,
+            lights,
+            environment,
+            generate,
+            roulette: sampler.get_1d(),
+        }
+    }
+}
+
+// Filter weighted average of a pixel's samples, and the variance of their weighted luminance
+// by Welford's method, which stays accurate without keeping the samples
+struct PixelStatistics {
+    count: i32,
+    sum: Color,
+    weight_sum: f64,
+    mean: f64,
+    m2: f64,
+}
+
+impl PixelStatistics {
+    fn new() -> Self {
+        Self {
+            count: 0,
+            sum: Color::zero(),
+            weight_sum: 0.0,
+            mean: 0.0,
+            m2: 0.0,
+        }
+    }
+
+    fn add(&mut self, sample: &Color, weight: f64) {
+        self.count += 1;
+        self.sum += &(weight * sample);
+        self.weight_sum += weight;
+
+        let luminance = weight * sample.luminance();
+        let delta = luminance - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (luminance - self.mean);
+    }
+
+    fn color(&self) -> Color {
+        // Filters with negative lobes can leave next to no total weight after a few samples
+        if self.weight_sum.abs() < 1e-8 {
             return Color::zero();
         }
+        &self.sum / self.weight_sum
+    }
+
+    fn converged(&self, threshold: f64) -> bool {
+        if self.count < 2 {
+            return false;
+        }
+
+        // Half width of the 95% confidence interval of the mean. Pixels near black are
+        // measured against a small floor, or they would never settle
+        let variance = self.m2 / (self.count - 1) as f64;
+        let half_width = 1.96 * f64::sqrt(variance / self.count as f64);
+        half_width <= threshold * self.mean.max(0.01)
+    }
+}
+
+// Blue through cyan, green and yellow to red as 't' goes from 0 to 1
+fn heat_color(t: f64) -> Color {
+    let t = t.clamp(0.0, 1.0);
+    let channel = |center: f64| (1.5 - f64::abs(4.0 * t - center)).clamp(0.0, 1.0);
+    Color::new(channel(3.0), channel(2.0), channel(1.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::{
+        environment::Gradient,
+        hittable_list::HittableList,
+        light::PointLight,
+        material::{Lambertian, Metal, MixMaterial},
+        sampler::IndependentSampler,
+        sphere::Sphere,
+        texture::SolidColor,
+    };
+
+    #[test]
+    fn mix_with_a_mirror_scales_direct_light_by_the_diffuse_weight() {
+        // A floor a quarter mirror and three quarters diffuse, lit from straight above, in a
+        // black environment where only the direct light reaches the eye
+        let amount = 0.25;
+        let albedo = 0.5;
+        let floor = MixMaterial::new(
+            Rc::new(Lambertian::new(Color::new(albedo, albedo, albedo))),
+            Rc::new(Metal::new(Color::new(1.0, 1.0, 1.0), 0.0)),
+            SolidColor::scalar(amount),
+        );
+
+        let mut world = HittableList::zero();
+        world.add(Sphere::new(
+            Point3::new(0.0, -1000.0, 0.0),
+            1000.0,
+            Rc::new(floor),
+        ));
+        let mut scene = Scene::new(world);
+        scene.add_light(PointLight::new(
+            Point3::new(0.0, 2.0, 0.0),
+            Color::new(4.0, 4.0, 4.0),
+        ));
+        scene.set_environment(Gradient::new(Color::zero(), Color::zero()));
+
+        let camera = Camera::new();
+        let mut sampler = IndependentSampler;
+        let ray = Ray::new(&Point3::new(0.0, 1.0, 1.0), &Vector3::new(0.0, -1.0, -1.0));
+        let n = 100_000;
+        let mut sum = 0.0;
+        for index in 0..n {
+            sampler.start_pixel_sample(0, 0, index);
+            sum += camera.ray_color(&ray, &scene, &mut sampler).y;
+        }
 
-        let unit_direction = Vector3::unit_vector(&ray.direction());
-        let a = 0.5 * (&unit_direction.y + 1.0);
-        &((1.0 - a) * &Color::new(1.0, 1.0, 1.0)) + &(a * &Color::new(0.5, 0.7, 1.0))
+        // The diffuse share of the floor, albedo / pi at normal incidence, under 4 / 2^2
+        let expected = (1.0 - amount) * albedo / std::f64::consts::PI;
+        let mean = sum / n as f64;
+        assert!(
+            (mean - expected).abs() < 0.02 * expected,
+            "{mean} vs {expected}"
+        );
     }
 }
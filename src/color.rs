@@ -10,6 +10,20 @@ impl Color {
         }
         0.0
     }
+
+    pub fn luminance(&self) -> f64 {
+        0.2126 * self.x + 0.7152 * self.y + 0.0722 * self.z
+    }
+
+    pub fn from_xyz(x: f64, y: f64, z: f64) -> Color {
+        // CIE XYZ to linear sRGB (D65 white point)
+        Color::new(
+            3.2406 * x - 1.5372 * y - 0.4986 * z,
+            -0.9689 * x + 1.8758 * y + 0.0415 * z,
+            0.0557 * x - 0.2040 * y + 1.0570 * z,
+        )
+    }
+
     pub fn write_color<W: Write>(out: &mut W, pixel_color: &Color) -> Result<(), io::Error> {
         let Color { x: r, y: g, z: b } = pixel_color;
 
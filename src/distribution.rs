@@ -0,0 +1,175 @@
+pub struct Distribution1D {
+    func: Vec<f64>,
+    cdf: Vec<f64>,
+    func_int: f64,
+}
+
+impl Distribution1D {
+    pub fn new(func: &[f64]) -> Self {
+        // Piecewise-constant distribution over [0, 1] with one bucket per value
+        let n = func.len();
+        let mut cdf = vec![0.0; n + 1];
+        for i in 1..=n {
+            cdf[i] = cdf[i - 1] + func[i - 1].max(0.0) / n as f64;
+        }
+
+        let func_int = cdf[n];
+        if func_int == 0.0 {
+            // Fall back to uniform sampling when the function is zero everywhere
+            for (i, c) in cdf.iter_mut().enumerate() {
+                *c = i as f64 / n as f64;
+            }
+        } else {
+            for c in cdf.iter_mut() {
+                *c /= func_int;
+            }
+        }
+
+        Self {
+            func: func.iter().map(|f| f.max(0.0)).collect(),
+            cdf,
+            func_int,
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.func.len()
+    }
+
+    pub fn integral(&self) -> f64 {
+        self.func_int
+    }
+
+    pub fn pdf(&self, offset: usize) -> f64 {
+        if self.func_int == 0.0 {
+            return 1.0;
+        }
+        self.func[offset] / self.func_int
+    }
+
+    // Returns the sampled point in [0, 1), its density and the bucket it fell into
+    pub fn sample_continuous(&self, u: f64) -> (f64, f64, usize) {
+        // Find the last cdf entry that is <= u
+        let offset = self
+            .cdf
+            .partition_point(|&c| c <= u)
+            .saturating_sub(1)
+            .min(self.count() - 1);
+
+        let mut du = u - self.cdf[offset];
+        let width = self.cdf[offset + 1] - self.cdf[offset];
+        if width > 0.0 {
+            du /= width;
+        }
+
+        let x = (offset as f64 + du) / self.count() as f64;
+        (x, self.pdf(offset), offset)
+    }
+}
+
+pub struct Distribution2D {
+    conditional: Vec<Distribution1D>,
+    marginal: Distribution1D,
+}
+
+impl Distribution2D {
+    // 'func' is laid out in rows of 'width' values, 'height' rows in total
+    pub fn new(func: &[f64], width: usize, height: usize) -> Self {
+        let conditional: Vec<Distribution1D> = (0..height)
+            .map(|v| Distribution1D::new(&func[v * width..(v + 1) * width]))
+            .collect();
+        let marginal_func: Vec<f64> = conditional.iter().map(|d| d.integral()).collect();
+
+        Self {
+            conditional,
+            marginal: Distribution1D::new(&marginal_func),
+        }
+    }
+
+    // Returns the sampled (u, v) point in [0, 1)^2 and its density
+    pub fn sample_continuous(&self, u0: f64, u1: f64) -> ((f64, f64), f64) {
+        let (v, pdf_v, row) = self.marginal.sample_continuous(u1);
+        let (u, pdf_u, _) = self.conditional[row].sample_continuous(u0);
+
+        ((u, v), pdf_u * pdf_v)
+    }
+
+    pub fn pdf(&self, u: f64, v: f64) -> f64 {
+        let height = self.marginal.count();
+        let row = ((v * height as f64) as usize).min(height - 1);
+        let width = self.conditional[row].count();
+        let column = ((u * width as f64) as usize).min(width - 1);
+
+        self.conditional[row].pdf(column) * self.marginal.pdf(row)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FUNC: [f64; 6] = [1.0, 0.0, 3.0, 2.0, 0.0, 4.0];
+
+    #[test]
+    fn pdf_integrates_to_one() {
+        let distribution = Distribution1D::new(&FUNC);
+        let n = distribution.count();
+        let integral: f64 = (0..n).map(|i| distribution.pdf(i) / n as f64).sum();
+        assert!((integral - 1.0).abs() < 1e-12);
+
+        let distribution = Distribution2D::new(&FUNC, 3, 2);
+        let mut integral = 0.0;
+        for y in 0..2 {
+            for x in 0..3 {
+                integral += distribution.pdf((x as f64 + 0.5) / 3.0, (y as f64 + 0.5) / 2.0) / 6.0;
+            }
+        }
+        assert!((integral - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn sample_continuous_inverts_the_cdf() {
+        let distribution = Distribution1D::new(&FUNC);
+        for k in 0..100 {
+            let u = (k as f64 + 0.5) / 100.0;
+            let (x, pdf, offset) = distribution.sample_continuous(u);
+
+            // Integrating the pdf up to the sampled point gives back 'u'
+            let n = distribution.count() as f64;
+            let below: f64 = (0..offset).map(|i| distribution.pdf(i) / n).sum();
+            let cdf = below + (x - offset as f64 / n) * pdf;
+            assert!((cdf - u).abs() < 1e-9, "cdf({x}) = {cdf}, expected {u}");
+            assert_eq!(pdf, distribution.pdf(offset));
+        }
+    }
+
+    #[test]
+    fn zero_buckets_are_never_sampled() {
+        let distribution = Distribution1D::new(&FUNC);
+        for k in 0..1000 {
+            let (_, pdf, offset) = distribution.sample_continuous(k as f64 / 1000.0);
+            assert!(FUNC[offset] > 0.0 && pdf > 0.0);
+        }
+
+        // The middle row and the first column of each row are zero
+        let func = [0.0, 1.0, 2.0, 0.0, 0.0, 0.0, 0.0, 3.0, 1.0];
+        let distribution = Distribution2D::new(&func, 3, 3);
+        for a in 0..50 {
+            for b in 0..50 {
+                let (u0, u1) = (a as f64 / 50.0, b as f64 / 50.0);
+                let ((u, v), pdf) = distribution.sample_continuous(u0, u1);
+                let cell = (v * 3.0) as usize * 3 + (u * 3.0) as usize;
+                assert!(func[cell] > 0.0 && pdf > 0.0, "sampled empty cell {cell}");
+            }
+        }
+    }
+
+    #[test]
+    fn zero_function_falls_back_to_uniform() {
+        let distribution = Distribution1D::new(&[0.0; 4]);
+        assert_eq!(distribution.integral(), 0.0);
+        let (x, pdf, offset) = distribution.sample_continuous(0.6);
+        assert!((x - 0.6).abs() < 1e-12);
+        assert_eq!((pdf, offset), (1.0, 2));
+    }
+}
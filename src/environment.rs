@@ -0,0 +1,138 @@
+use std::{f64::consts::PI, io};
+
+use crate::{color::Color, distribution::Distribution2D, image::Image, vector3::Vector3};
+
+pub struct EnvironmentSample {
+    // Unit direction towards the environment
+    pub direction: Vector3,
+    pub radiance: Color,
+    // Density with respect to solid angle
+    pub pdf: f64,
+}
+
+pub trait Environment {
+    // Radiance arriving from infinitely far away along 'direction'
+    fn radiance(&self, direction: &Vector3) -> Color;
+
+    // Environments that return None here are only found by rays that escape the scene
+    fn sample(&self, _u: (f64, f64)) -> Option<EnvironmentSample> {
+        None
+    }
+
+    fn pdf(&self, _direction: &Vector3) -> f64 {
+        0.0
+    }
+}
+
+pub struct Gradient {
+    bottom: Color,
+    top: Color,
+}
+
+impl Gradient {
+    pub fn new(bottom: Color, top: Color) -> Self {
+        Self { bottom, top }
+    }
+}
+
+impl Environment for Gradient {
+    fn radiance(&self, direction: &Vector3) -> Color {
+        let unit_direction = Vector3::unit_vector(direction);
+        let a = 0.5 * (unit_direction.y + 1.0);
+        &((1.0 - a) * &self.bottom) + &(a * &self.top)
+    }
+}
+
+pub struct EnvironmentMap {
+    image: Image,
+    intensity: f64,
+    distribution: Distribution2D,
+}
+
+impl EnvironmentMap {
+    pub fn new(image: Image, intensity: f64) -> Self {
+        let (width, height) = (image.width(), image.height());
+
+        // Weight each texel by its luminance and by sin(theta), which accounts for the rows
+        // near the poles covering less solid angle
+        let mut func = Vec::with_capacity(width * height);
+        for y in 0..height {
+            let sin_theta = f64::sin(PI * (y as f64 + 0.5) / height as f64);
+            for x in 0..width {
+                func.push(image.pixel(x, y).luminance() * sin_theta);
+            }
+        }
+
+        Self {
+            distribution: Distribution2D::new(&func, width, height),
+            image,
+            intensity,
+        }
+    }
+
+    // Loads an equirectangular Radiance (.hdr) image
+    pub fn load(path: &str, intensity: f64) -> io::Result<Self> {
+        Ok(EnvironmentMap::new(Image::load_hdr(path)?, intensity))
+    }
+
+    fn direction_to_uv(direction: &Vector3) -> (f64, f64) {
+        let d = Vector3::unit_vector(direction);
+        let theta = f64::acos(d.y.clamp(-1.0, 1.0));
+        let phi = f64::atan2(d.z, d.x) + PI;
+
+        (phi / (2.0 * PI), theta / PI)
+    }
+
+    fn uv_to_direction(u: f64, v: f64) -> Vector3 {
+        let theta = v * PI;
+        let phi = u * 2.0 * PI - PI;
+
+        Vector3::new(
+            f64::sin(theta) * f64::cos(phi),
+            f64::cos(theta),
+            f64::sin(theta) * f64::sin(phi),
+        )
+    }
+
+    fn lookup(&self, u: f64, v: f64) -> Color {
+        let x = (u * self.image.width() as f64) as usize;
+        let y = (v * self.image.height() as f64) as usize;
+        self.intensity * &self.image.pixel(x, y)
+    }
+}
+
+impl Environment for EnvironmentMap {
+    fn radiance(&self, direction: &Vector3) -> Color {
+        let (u, v) = EnvironmentMap::direction_to_uv(direction);
+        self.lookup(u, v)
+    }
+
+    fn sample(&self, sample: (f64, f64)) -> Option<EnvironmentSample> {
+        let ((u, v), map_pdf) = self.distribution.sample_continuous(sample.0, sample.1);
+        if map_pdf <= 0.0 {
+            return None;
+        }
+
+        // Convert the density from the unit square to solid angle
+        let sin_theta = f64::sin(v * PI);
+        if sin_theta <= 0.0 {
+            return None;
+        }
+
+        Some(EnvironmentSample {
+            direction: EnvironmentMap::uv_to_direction(u, v),
+            radiance: self.lookup(u, v),
+            pdf: map_pdf / (2.0 * PI * PI * sin_theta),
+        })
+    }
+
+    fn pdf(&self, direction: &Vector3) -> f64 {
+        let (u, v) = EnvironmentMap::direction_to_uv(direction);
+        let sin_theta = f64::sin(v * PI);
+        if sin_theta <= 0.0 {
+            return 0.0;
+        }
+
+        self.distribution.pdf(u, v) / (2.0 * PI * PI * sin_theta)
+    }
+}
@@ -0,0 +1,244 @@
+use std::f64::consts::PI;
+
+use crate::distribution::Distribution2D;
+
+// Pixel reconstruction filter, which weights each sample by its offset from the pixel center.
+// Filters reach 'radius' pixels out on both axes, and the wider ones blend in samples from
+// under the neighboring pixels
+#[derive(Clone, Copy)]
+pub enum Filter {
+    // Plain average of the samples, the sharpest but most prone to aliasing at radius 0.5
+    Box { radius: f64 },
+    // Soft, with no ringing; shifted down to reach zero at the radius
+    Gaussian { radius: f64, sigma: f64 },
+    // Mitchell-Netravali cubic, trading blur against ringing with 'b' and 'c'. One third for
+    // both is the usual choice
+    Mitchell { radius: f64, b: f64, c: f64 },
+    // Sinc windowed by a wider sinc, usually with a radius of 2 or 3. Sharp with slight
+    // ringing, but its negative lobes make it noisier at low sample counts
+    Lanczos { radius: f64 },
+    // Blackman-Harris window, close to a Gaussian with less blur
+    BlackmanHarris { radius: f64 },
+}
+
+impl Filter {
+    pub fn radius(&self) -> f64 {
+        match *self {
+            Filter::Box { radius }
+            | Filter::Gaussian { radius, .. }
+            | Filter::Mitchell { radius, .. }
+            | Filter::Lanczos { radius }
+            | Filter::BlackmanHarris { radius } => radius,
+        }
+    }
+
+    // Weight of a sample 'x', 'y' pixels from the pixel center. Mitchell and Lanczos filters
+    // have negative lobes
+    pub fn evaluate(&self, x: f64, y: f64) -> f64 {
+        self.evaluate_1d(x) * self.evaluate_1d(y)
+    }
+
+    fn evaluate_1d(&self, x: f64) -> f64 {
+        let radius = self.radius();
+        if x.abs() > radius {
+            return 0.0;
+        }
+
+        match *self {
+            Filter::Box { .. } => 1.0,
+            Filter::Gaussian { sigma, .. } => {
+                let gaussian = |x: f64| f64::exp(-x * x / (2.0 * sigma * sigma));
+                f64::max(0.0, gaussian(x) - gaussian(radius))
+            }
+            Filter::Mitchell { b, c, .. } => {
+                // The cubic spans two units on either side, stretched to the radius
+                let x = 2.0 * x.abs() / radius;
+                if x > 1.0 {
+                    ((-b - 6.0 * c) * x * x * x
+                        + (6.0 * b + 30.0 * c) * x * x
+                        + (-12.0 * b - 48.0 * c) * x
+                        + (8.0 * b + 24.0 * c))
+                        / 6.0
+                } else {
+                    ((12.0 - 9.0 * b - 6.0 * c) * x * x * x
+                        + (-18.0 + 12.0 * b + 6.0 * c) * x * x
+                        + (6.0 - 2.0 * b))
+                        / 6.0
+                }
+            }
+            Filter::Lanczos { .. } => sinc(x) * sinc(x / radius),
+            Filter::BlackmanHarris { .. } => {
+                let t = 2.0 * PI * (x + radius) / (2.0 * radius);
+                0.35875 - 0.48829 * f64::cos(t) + 0.14128 * f64::cos(2.0 * t)
+                    - 0.01168 * f64::cos(3.0 * t)
+            }
+        }
+    }
+}
+
+fn sinc(x: f64) -> f64 {
+    if x.abs() < 1e-5 {
+        return 1.0;
+    }
+    f64::sin(PI * x) / (PI * x)
+}
+
+// Draws pixel sample offsets in proportion to the filter's magnitude, so that the samples all
+// carry about the same weight instead of wasting effort where the filter is small
+pub struct FilterSampler {
+    filter: Filter,
+    // Tabulated magnitude of the filter over its square, except for box filters
+    distribution: Option<Distribution2D>,
+}
+
+// Table entries per pixel along each axis
+const FILTER_TABLE_RESOLUTION: f64 = 32.0;
+
+impl FilterSampler {
+    pub fn new(filter: Filter) -> Self {
+        let distribution = match filter {
+            Filter::Box { .. } => None,
+            _ => {
+                let radius = filter.radius();
+                let size = ((2.0 * radius * FILTER_TABLE_RESOLUTION).ceil() as usize).max(1);
+                let mut func = Vec::with_capacity(size * size);
+                for y in 0..size {
+                    for x in 0..size {
+                        let px = ((x as f64 + 0.5) / size as f64 * 2.0 - 1.0) * radius;
+                        let py = ((y as f64 + 0.5) / size as f64 * 2.0 - 1.0) * radius;
+                        func.push(filter.evaluate(px, py).abs());
+                    }
+                }
+                Some(Distribution2D::new(&func, size, size))
+            }
+        };
+
+        Self {
+            filter,
+            distribution,
+        }
+    }
+
+    // Maps the sample 'u' to an offset from the pixel center, in pixels, and the weight of the
+    // sample taken there
+    pub fn sample(&self, u: (f64, f64)) -> (f64, f64, f64) {
+        let radius = self.filter.radius();
+        let Some(distribution) = &self.distribution else {
+            return ((2.0 * u.0 - 1.0) * radius, (2.0 * u.1 - 1.0) * radius, 1.0);
+        };
+
+        let ((s, t), pdf) = distribution.sample_continuous(u.0, u.1);
+        if pdf <= 0.0 {
+            return (0.0, 0.0, 0.0);
+        }
+
+        let (x, y) = ((2.0 * s - 1.0) * radius, (2.0 * t - 1.0) * radius);
+        // The table's density is over the unit square, which covers the filter's 2r by 2r
+        let pdf = pdf / (4.0 * radius * radius);
+        (x, y, self.filter.evaluate(x, y) / pdf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILTERS: [Filter; 5] = [
+        Filter::Box { radius: 0.5 },
+        Filter::Gaussian {
+            radius: 1.5,
+            sigma: 0.5,
+        },
+        Filter::Mitchell {
+            radius: 2.0,
+            b: 1.0 / 3.0,
+            c: 1.0 / 3.0,
+        },
+        Filter::Lanczos { radius: 3.0 },
+        Filter::BlackmanHarris { radius: 1.5 },
+    ];
+
+    // Midpoint rule over the filter's square, of 'f' applied to the filter's value
+    fn integrate(filter: &Filter, f: impl Fn(f64) -> f64) -> f64 {
+        const N: usize = 600;
+        let radius = filter.radius();
+        let step = 2.0 * radius / N as f64;
+        let mut sum = 0.0;
+        for a in 0..N {
+            for b in 0..N {
+                let x = -radius + (a as f64 + 0.5) * step;
+                let y = -radius + (b as f64 + 0.5) * step;
+                sum += f(filter.evaluate(x, y));
+            }
+        }
+        sum * step * step
+    }
+
+    // Offsets and weights for a stratified grid of sample values
+    fn samples(filter: Filter) -> Vec<(f64, f64, f64)> {
+        const N: usize = 512;
+        let sampler = FilterSampler::new(filter);
+        let mut samples = Vec::with_capacity(N * N);
+        for a in 0..N {
+            for b in 0..N {
+                let u = ((a as f64 + 0.5) / N as f64, (b as f64 + 0.5) / N as f64);
+                samples.push(sampler.sample(u));
+            }
+        }
+        samples
+    }
+
+    #[test]
+    fn sample_weights_average_to_the_filter_integral() {
+        // The camera divides by the sum of the weights, so they only need to estimate the
+        // filter's integral without bias, negative lobes included
+        for filter in FILTERS {
+            let expected = integrate(&filter, |f| f);
+            let samples = samples(filter);
+            let mean = samples.iter().map(|s| s.2).sum::<f64>() / samples.len() as f64;
+            assert!(
+                (mean - expected).abs() < 0.01 * expected.abs(),
+                "{}: {mean} against {expected}",
+                filter.radius()
+            );
+
+            let radius = filter.radius();
+            assert!(samples
+                .iter()
+                .all(|&(x, y, _)| x.abs() <= radius && y.abs() <= radius));
+        }
+    }
+
+    #[test]
+    fn samples_follow_the_filter_magnitude() {
+        // Drawing in proportion to |f| gives the weights the sign of the filter where the
+        // sample landed, and magnitudes that average to the integral of |f|
+        for filter in FILTERS {
+            let expected = integrate(&filter, f64::abs);
+            let samples = samples(filter);
+            for &(x, y, weight) in samples.iter() {
+                let value = filter.evaluate(x, y);
+                assert!(value == 0.0 || weight.signum() == value.signum());
+            }
+
+            let mean = samples.iter().map(|s| s.2.abs()).sum::<f64>() / samples.len() as f64;
+            assert!(
+                (mean - expected).abs() < 0.01 * expected,
+                "{}: {mean} against {expected}",
+                filter.radius()
+            );
+        }
+    }
+
+    #[test]
+    fn negative_lobes_give_negative_weights() {
+        for filter in [FILTERS[2], FILTERS[3]] {
+            let samples = samples(filter);
+            let negative = samples.iter().filter(|s| s.2 < 0.0).count();
+            assert!(negative > 0 && negative < samples.len() / 2);
+        }
+        for filter in [FILTERS[0], FILTERS[1], FILTERS[4]] {
+            assert!(samples(filter).iter().all(|s| s.2 >= 0.0));
+        }
+    }
+}
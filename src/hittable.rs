@@ -3,6 +3,7 @@ use crate::{
     interval::Interval,
     material::{Lambertian, Material},
     ray::Ray,
+    utils,
     vector3::{Point3, Vector3},
 };
 use std::rc::Rc;
@@ -13,6 +14,11 @@ pub struct HitRecord {
     pub normal: Vector3,
     pub mat: Rc<dyn Material>,
     pub t: f64,
+    pub u: f64,
+    pub v: f64,
+    // Derivatives of 'p' along the texture coordinates u and v, spanning the tangent plane
+    pub tangent: Vector3,
+    pub bitangent: Vector3,
     pub front_face: bool,
 }
 
@@ -23,6 +29,10 @@ impl HitRecord {
             normal: Vector3::zero(),
             mat: Rc::new(Lambertian::new(Color::new(0.8, 0.8, 0.0))),
             t: 0.0,
+            u: 0.0,
+            v: 0.0,
+            tangent: Vector3::zero(),
+            bitangent: Vector3::zero(),
             front_face: false,
         }
     }
@@ -31,15 +41,80 @@ impl HitRecord {
         // Setst the hits record normal vector
         // ! NOTE: the parameter 'outward_normal' is assumed to have a unit length
 
-        self.front_face = Vector3::dot(&ray.direction(), &outward_normal) < 0.0;
+        self.front_face = Vector3::dot(&ray.direction(), outward_normal) < 0.0;
         self.normal = if self.front_face {
             *outward_normal
         } else {
             -*outward_normal
         }
     }
+
+    pub fn is_opaque(&self, ray: &Ray) -> bool {
+        // Alpha tests the hit against its material, where fractional opacity is the chance of
+        // the surface being there. Shapes skip hits that fail, so the closest hit of a group is
+        // always the closest opaque one
+        let alpha = self.mat.alpha(self);
+        if alpha >= 1.0 {
+            return true;
+        }
+        if alpha <= 0.0 {
+            return false;
+        }
+
+        // The ray's alpha sample is hashed with the hit point, so that the surfaces one ray
+        // crosses are each kept or cut out independently
+        let hash = utils::mix_bits(
+            ray.alpha_sample().to_bits()
+                ^ utils::mix_bits(
+                    self.p.x.to_bits()
+                        ^ utils::mix_bits(self.p.y.to_bits() ^ utils::mix_bits(self.p.z.to_bits())),
+                ),
+        );
+        ((hash >> 11) as f64 / (1u64 << 53) as f64) < alpha
+    }
 }
 
 pub trait Hittable {
     fn hit(&self, ray: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        hittable_list::HittableList, material::AlphaMask, quad::Quad, texture::SolidColor,
+    };
+
+    #[test]
+    fn stacked_cutouts_are_alpha_tested_independently() {
+        // Two half opaque squares, one above the other, both crossed by every ray
+        let leaf = Rc::new(AlphaMask::new(
+            Rc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+            SolidColor::scalar(0.5),
+        ));
+        let mut world = HittableList::zero();
+        for y in [0.0, 1.0] {
+            world.add(Quad::new(
+                Point3::new(-1.0, y, -1.0),
+                Vector3::new(2.0, 0.0, 0.0),
+                Vector3::new(0.0, 0.0, 2.0),
+                leaf.clone(),
+            ));
+        }
+
+        let n = 100_000;
+        let mut passed = 0;
+        for _ in 0..n {
+            let ray = Ray::new(&Point3::new(0.0, 2.0, 0.0), &Vector3::new(0.0, -1.0, 0.0))
+                .with_alpha_sample(utils::canonical_random_number());
+            let mut rec = HitRecord::zero();
+            if !world.hit(&ray, Interval::new(0.001, f64::INFINITY), &mut rec) {
+                passed += 1;
+            }
+        }
+
+        // Each square lets half of the rays through
+        let transmission = passed as f64 / n as f64;
+        assert!((transmission - 0.25).abs() < 0.01, "{transmission}");
+    }
+}
@@ -8,6 +8,7 @@ pub struct HittableList {
 }
 
 impl HittableList {
+    #[allow(dead_code)]
     pub fn new(object: impl Hittable + 'static) -> Self {
         Self {
             objects: vec![Box::new(object)],
@@ -18,6 +19,7 @@ impl HittableList {
         Self { objects: vec![] }
     }
 
+    #[allow(dead_code)]
     pub fn clear(&mut self) {
         self.objects.clear();
     }
@@ -0,0 +1,285 @@
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Read, Write},
+};
+
+use crate::color::Color;
+
+pub struct Image {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>,
+}
+
+impl Image {
+    pub fn new(width: usize, height: usize, pixels: Vec<Color>) -> Self {
+        // Pixels are stored row by row, top to bottom
+        assert_eq!(pixels.len(), width * height);
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> Color {
+        let x = x.min(self.width - 1);
+        let y = y.min(self.height - 1);
+        self.pixels[y * self.width + x]
+    }
+
+    // Places 'right' to the right of this image; both must have the same height
+    pub fn side_by_side(&self, right: &Image) -> Image {
+        let mut pixels = Vec::with_capacity(self.pixels.len() + right.pixels.len());
+        for y in 0..self.height {
+            pixels.extend_from_slice(&self.pixels[y * self.width..(y + 1) * self.width]);
+            pixels.extend_from_slice(&right.pixels[y * right.width..(y + 1) * right.width]);
+        }
+
+        Image::new(self.width + right.width, self.height, pixels)
+    }
+
+    // Places 'bottom' below this image; both must have the same width
+    pub fn over_under(&self, bottom: &Image) -> Image {
+        let mut pixels = self.pixels.clone();
+        pixels.extend_from_slice(&bottom.pixels);
+
+        Image::new(self.width, self.height + bottom.height, pixels)
+    }
+
+    // Writes a plain (P3) PPM image, gamma corrected
+    pub fn write_ppm(&self, path: &str) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+
+        writeln!(writer, "P3\n{} {}\n255", self.width, self.height)?;
+        for pixel in self.pixels.iter() {
+            Color::write_color(&mut writer, pixel)?;
+        }
+
+        writer.flush()
+    }
+
+    pub fn load_hdr(path: &str) -> io::Result<Image> {
+        // Reads a Radiance RGBE (.hdr) image, flat or run-length encoded
+        let mut reader = BufReader::new(File::open(path)?);
+
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        if !line.starts_with("#?") {
+            return Err(invalid_data("missing Radiance header"));
+        }
+
+        // Header lines run until the first empty line
+        loop {
+            line.clear();
+            if reader.read_line(&mut line)? == 0 {
+                return Err(invalid_data("unexpected end of header"));
+            }
+            let entry = line.trim();
+            if entry.is_empty() {
+                break;
+            }
+            if entry.starts_with("FORMAT=") && entry != "FORMAT=32-bit_rle_rgbe" {
+                return Err(invalid_data("unsupported pixel format"));
+            }
+        }
+
+        // Only the standard top-to-bottom, left-to-right orientation is supported
+        line.clear();
+        reader.read_line(&mut line)?;
+        let resolution: Vec<&str> = line.split_whitespace().collect();
+        let (height, width) = match resolution.as_slice() {
+            ["-Y", h, "+X", w] => (parse_dimension(h)?, parse_dimension(w)?),
+            _ => return Err(invalid_data("unsupported resolution line")),
+        };
+
+        let mut pixels = Vec::with_capacity(width * height);
+        let mut scanline = vec![[0u8; 4]; width];
+        for _ in 0..height {
+            read_scanline(&mut reader, &mut scanline)?;
+            pixels.extend(scanline.iter().map(rgbe_to_color));
+        }
+
+        Ok(Image {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    pub fn load_ppm(path: &str) -> io::Result<Image> {
+        // Reads a plain (P3) or binary (P6) PPM image. Values are scaled to [0, 1] but otherwise
+        // kept as stored, since normal and bump maps hold data rather than gamma encoded colors
+        let mut bytes = Vec::new();
+        File::open(path)?.read_to_end(&mut bytes)?;
+
+        let mut position = 0;
+        let mut header = Vec::with_capacity(4);
+        while header.len() < 4 {
+            header.push(next_token(&bytes, &mut position)?);
+        }
+
+        let binary = match header[0].as_str() {
+            "P3" => false,
+            "P6" => true,
+            _ => return Err(invalid_data("unsupported PPM format")),
+        };
+        let width = parse_dimension(&header[1])?;
+        let height = parse_dimension(&header[2])?;
+        let max_value = parse_dimension(&header[3])?;
+        if max_value > 65535 {
+            return Err(invalid_data("invalid PPM maximum value"));
+        }
+
+        let count = 3 * width * height;
+        let mut samples = Vec::with_capacity(count);
+        if binary {
+            // A single whitespace character separates the header from the pixel data
+            position += 1;
+            let sample_size = if max_value > 255 { 2 } else { 1 };
+            let data = bytes
+                .get(position..position + count * sample_size)
+                .ok_or_else(|| invalid_data("truncated PPM pixel data"))?;
+            samples.extend(data.chunks(sample_size).map(|sample| {
+                sample
+                    .iter()
+                    .fold(0, |value, &byte| (value << 8) | byte as usize)
+            }));
+        } else {
+            for _ in 0..count {
+                let token = next_token(&bytes, &mut position)?;
+                let sample = token
+                    .parse::<usize>()
+                    .map_err(|_| invalid_data("invalid PPM sample"))?;
+                samples.push(sample.min(max_value));
+            }
+        }
+
+        let scale = 1.0 / max_value as f64;
+        let pixels = samples
+            .chunks(3)
+            .map(|rgb| {
+                Color::new(
+                    rgb[0] as f64 * scale,
+                    rgb[1] as f64 * scale,
+                    rgb[2] as f64 * scale,
+                )
+            })
+            .collect();
+
+        Ok(Image {
+            width,
+            height,
+            pixels,
+        })
+    }
+}
+
+// Reads the next whitespace separated token of a PPM header, skipping '#' comments
+fn next_token(bytes: &[u8], position: &mut usize) -> io::Result<String> {
+    loop {
+        match bytes.get(*position) {
+            Some(b'#') => {
+                while bytes.get(*position).is_some_and(|&b| b != b'\n') {
+                    *position += 1;
+                }
+            }
+            Some(b) if b.is_ascii_whitespace() => *position += 1,
+            Some(_) => break,
+            None => return Err(invalid_data("unexpected end of PPM data")),
+        }
+    }
+
+    let start = *position;
+    while bytes
+        .get(*position)
+        .is_some_and(|b| !b.is_ascii_whitespace())
+    {
+        *position += 1;
+    }
+
+    Ok(String::from_utf8_lossy(&bytes[start..*position]).into_owned())
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn parse_dimension(s: &str) -> io::Result<usize> {
+    match s.parse::<usize>() {
+        Ok(n) if n > 0 => Ok(n),
+        _ => Err(invalid_data("invalid image dimension")),
+    }
+}
+
+fn read_scanline<R: Read>(reader: &mut R, scanline: &mut [[u8; 4]]) -> io::Result<()> {
+    let width = scanline.len();
+    let mut first = [0u8; 4];
+    reader.read_exact(&mut first)?;
+
+    // New-style run-length encoding starts each scanline with 2, 2 and the width
+    let is_rle = (8..0x8000).contains(&width)
+        && first[0] == 2
+        && first[1] == 2
+        && ((first[2] as usize) << 8 | first[3] as usize) == width;
+
+    if !is_rle {
+        scanline[0] = first;
+        for pixel in scanline.iter_mut().skip(1) {
+            reader.read_exact(pixel)?;
+        }
+        return Ok(());
+    }
+
+    // Each of the four channels is stored separately as runs and literals
+    for channel in 0..4 {
+        let mut x = 0;
+        while x < width {
+            let mut count = [0u8; 1];
+            reader.read_exact(&mut count)?;
+            let count = count[0] as usize;
+
+            if count > 128 {
+                let run = count - 128;
+                if x + run > width {
+                    return Err(invalid_data("bad run-length in scanline"));
+                }
+                let mut value = [0u8; 1];
+                reader.read_exact(&mut value)?;
+                for pixel in &mut scanline[x..x + run] {
+                    pixel[channel] = value[0];
+                }
+                x += run;
+            } else {
+                if count == 0 || x + count > width {
+                    return Err(invalid_data("bad literal count in scanline"));
+                }
+                let mut values = vec![0u8; count];
+                reader.read_exact(&mut values)?;
+                for (pixel, value) in scanline[x..x + count].iter_mut().zip(values) {
+                    pixel[channel] = value;
+                }
+                x += count;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn rgbe_to_color(rgbe: &[u8; 4]) -> Color {
+    if rgbe[3] == 0 {
+        return Color::zero();
+    }
+
+    let f = f64::powi(2.0, rgbe[3] as i32 - (128 + 8));
+    Color::new(rgbe[0] as f64 * f, rgbe[1] as f64 * f, rgbe[2] as f64 * f)
+}
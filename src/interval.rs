@@ -4,6 +4,7 @@ pub struct Interval {
 }
 
 impl Interval {
+    #[allow(dead_code)]
     pub fn new_full_range() -> Self {
         Self {
             min: f64::NEG_INFINITY,
@@ -15,10 +16,12 @@ impl Interval {
         Self { min, max }
     }
 
+    #[allow(dead_code)]
     pub fn size(&self) -> f64 {
         self.max - self.min
     }
 
+    #[allow(dead_code)]
     pub fn contains(&self, x: f64) -> bool {
         self.min <= x && x <= self.max
     }
@@ -0,0 +1,453 @@
+use std::{fs, io};
+
+use crate::{
+    ray::Ray,
+    utils,
+    vector3::{Point3, Vector3},
+};
+
+// Radial bands across the film, each with its own exit pupil bounds
+const PUPIL_BANDS: usize = 64;
+// Rays traced from each band when bounding its exit pupil
+const PUPIL_SAMPLES: usize = 4096;
+
+// One spherical surface of a lens prescription, in millimeters
+struct LensInterface {
+    // Zero for the aperture stop, which is flat
+    curvature_radius: f64,
+    // Distance along the axis to the next interface, or to the film for the last one
+    thickness: f64,
+    // Index of refraction behind the interface, towards the film
+    eta: f64,
+    aperture_radius: f64,
+}
+
+impl LensInterface {
+    fn is_stop(&self) -> bool {
+        self.curvature_radius == 0.0
+    }
+}
+
+// Rectangle on the plane of the rear element that the rays leaving a film band pass through
+#[derive(Clone, Copy)]
+struct PupilBounds {
+    min: (f64, f64),
+    max: (f64, f64),
+}
+
+impl PupilBounds {
+    fn contains(&self, x: f64, y: f64) -> bool {
+        (self.min.0..=self.max.0).contains(&x) && (self.min.1..=self.max.1).contains(&y)
+    }
+
+    fn union(&self, x: f64, y: f64) -> PupilBounds {
+        PupilBounds {
+            min: (self.min.0.min(x), self.min.1.min(y)),
+            max: (self.max.0.max(x), self.max.1.max(y)),
+        }
+    }
+
+    fn area(&self) -> f64 {
+        (self.max.0 - self.min.0) * (self.max.1 - self.min.1)
+    }
+}
+
+// A sequence of spherical lens elements in front of the film, after Kolb et al. (1995) and
+// pbrt's realistic camera. Rays are traced in lens space, in millimeters, with the film at the
+// origin and the lens extending towards -z, the direction the camera looks in
+pub struct LensSystem {
+    // Ordered from the front of the lens, facing the scene, to the rear, facing the film
+    interfaces: Vec<LensInterface>,
+    // Width of the film in millimeters, 36 for full frame
+    pub film_width: f64,
+    film_height: f64,
+    // Distance from the rear element to the film, which focusing sets in place of the
+    // prescription's last thickness
+    film_distance: f64,
+    exit_pupils: Vec<PupilBounds>,
+    // Area of the exit pupil seen from the center of the film, which the weights are relative to
+    center_pupil_area: f64,
+}
+
+impl LensSystem {
+    // Reads a prescription with one interface per line, front to back: curvature radius,
+    // thickness, index of refraction and aperture diameter, in millimeters. The aperture stop
+    // has a zero radius, and an index of 0 or 1 stands for air. Lines starting with '#' are
+    // comments
+    pub fn load(path: &str) -> io::Result<Self> {
+        let mut interfaces = Vec::new();
+
+        for line in fs::read_to_string(path)?.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let values = line
+                .split_whitespace()
+                .map(|value| value.parse::<f64>().map_err(|_| invalid_data(line)))
+                .collect::<io::Result<Vec<f64>>>()?;
+            let [curvature_radius, thickness, eta, aperture] = values[..] else {
+                return Err(invalid_data(line));
+            };
+
+            interfaces.push(LensInterface {
+                curvature_radius,
+                thickness,
+                eta: if eta == 0.0 { 1.0 } else { eta },
+                aperture_radius: aperture / 2.0,
+            });
+        }
+
+        if interfaces.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "empty lens prescription",
+            ));
+        }
+
+        Ok(Self {
+            film_distance: interfaces.last().unwrap().thickness,
+            interfaces,
+            film_width: 36.0,
+            film_height: 24.0,
+            exit_pupils: vec![],
+            center_pupil_area: 1.0,
+        })
+    }
+
+    // Stops the lens down to an aperture stop 'diameter' millimeters wide
+    pub fn with_stop(mut self, diameter: f64) -> Self {
+        for interface in self.interfaces.iter_mut().filter(|i| i.is_stop()) {
+            interface.aperture_radius = interface.aperture_radius.min(diameter / 2.0);
+        }
+
+        self
+    }
+
+    // Moves the lens so that it focuses 'focus_dist' millimeters in front of the film, and
+    // bounds the exit pupil over a film with the given aspect ratio
+    pub fn prepare(&mut self, focus_dist: f64, aspect_ratio: f64) {
+        self.film_height = self.film_width / aspect_ratio;
+
+        // Focus from the prescription's spacing, so that preparing again lands in the same place
+        self.film_distance = self.interfaces.last().unwrap().thickness;
+        if let Some(film_distance) = self.focus_thick_lens(focus_dist) {
+            self.film_distance = film_distance;
+        }
+
+        let half_diagonal = self.half_diagonal();
+        self.exit_pupils = (0..PUPIL_BANDS)
+            .map(|band| {
+                self.bound_exit_pupil(
+                    half_diagonal * band as f64 / PUPIL_BANDS as f64,
+                    half_diagonal * (band + 1) as f64 / PUPIL_BANDS as f64,
+                )
+            })
+            .collect();
+
+        // Estimate how much of the center's bounds rays actually get through
+        let center = Point3::zero();
+        let passing = (0..PUPIL_SAMPLES)
+            .filter(|_| {
+                let u = (
+                    utils::canonical_random_number(),
+                    utils::canonical_random_number(),
+                );
+                let (p_rear, _) = self.sample_exit_pupil(0.0, 0.0, u);
+                self.trace_from_film(&Ray::new(&center, &(&p_rear - &center)))
+                    .is_some()
+            })
+            .count();
+        self.center_pupil_area =
+            self.exit_pupils[0].area() * passing.max(1) as f64 / PUPIL_SAMPLES as f64;
+    }
+
+    // Returns the lens space ray leaving the front of the lens towards the image position 's',
+    // 't', from 0 to 1 left to right and top to bottom, with the weight of its contribution.
+    // 'u' picks the point on the exit pupil. Rays blocked inside the lens return None
+    pub fn generate_ray(&self, s: f64, t: f64, u: (f64, f64)) -> Option<(Ray, f64)> {
+        // The lens flips the image, so the top left of the image lands on the bottom right of
+        // the film as seen from behind it
+        let p_film = Point3::new(
+            (0.5 - s) * self.film_width,
+            (t - 0.5) * self.film_height,
+            0.0,
+        );
+
+        let (p_rear, pupil_area) = self.sample_exit_pupil(p_film.x, p_film.y, u);
+        let film_ray = Ray::new(&p_film, &(&p_rear - &p_film));
+        let ray = self.trace_from_film(&film_ray)?;
+
+        // Irradiance falls off with cos^4 of the angle off the axis and grows with the area the
+        // pupil sample was drawn from, relative to the center of the film
+        let cos_theta = -Vector3::unit_vector(&film_ray.direction()).z;
+        let weight = cos_theta.powi(4) * pupil_area / self.center_pupil_area;
+
+        Some((ray, weight))
+    }
+
+    fn half_diagonal(&self) -> f64 {
+        f64::hypot(self.film_width, self.film_height) / 2.0
+    }
+
+    fn thickness(&self, i: usize) -> f64 {
+        // Spacing behind interface 'i', where the last one is focused
+        if i + 1 == self.interfaces.len() {
+            self.film_distance
+        } else {
+            self.interfaces[i].thickness
+        }
+    }
+
+    fn front_z(&self) -> f64 {
+        // Distance from the film to the front of the lens
+        (0..self.interfaces.len()).map(|i| self.thickness(i)).sum()
+    }
+
+    fn rear_z(&self) -> f64 {
+        // Distance from the film to the rear of the lens
+        self.film_distance
+    }
+
+    fn trace_from_film(&self, ray: &Ray) -> Option<Ray> {
+        let mut origin = ray.origin();
+        let mut direction = ray.direction();
+        let mut z = 0.0;
+
+        for (i, interface) in self.interfaces.iter().enumerate().rev() {
+            z -= self.thickness(i);
+
+            let (t, normal) = intersect_interface(interface, z, &origin, &direction)?;
+            origin = &origin + &(t * &direction);
+            if origin.x * origin.x + origin.y * origin.y
+                > interface.aperture_radius * interface.aperture_radius
+            {
+                return None;
+            }
+
+            if let Some(normal) = normal {
+                let eta_t = if i > 0 {
+                    self.interfaces[i - 1].eta
+                } else {
+                    1.0
+                };
+                direction = refract(&direction, &normal, interface.eta / eta_t)?;
+            }
+        }
+
+        Some(Ray::new(&origin, &direction))
+    }
+
+    fn trace_from_scene(&self, ray: &Ray) -> Option<Ray> {
+        let mut origin = ray.origin();
+        let mut direction = ray.direction();
+        let mut z = -self.front_z();
+
+        for (i, interface) in self.interfaces.iter().enumerate() {
+            let (t, normal) = intersect_interface(interface, z, &origin, &direction)?;
+            origin = &origin + &(t * &direction);
+            if origin.x * origin.x + origin.y * origin.y
+                > interface.aperture_radius * interface.aperture_radius
+            {
+                return None;
+            }
+
+            if let Some(normal) = normal {
+                let eta_i = if i > 0 {
+                    self.interfaces[i - 1].eta
+                } else {
+                    1.0
+                };
+                direction = refract(&direction, &normal, eta_i / interface.eta)?;
+            }
+
+            z += self.thickness(i);
+        }
+
+        Some(Ray::new(&origin, &direction))
+    }
+
+    fn focus_thick_lens(&self, focus_dist: f64) -> Option<f64> {
+        // Returns the distance from the rear of the lens to the film that focuses at 'focus_dist',
+        // treating the lens as a thick lens with the principal planes and focal points found by
+        // tracing a ray parallel to the axis through each side
+        let x = 0.001 * self.half_diagonal();
+
+        let scene_ray = Ray::new(
+            &Point3::new(x, 0.0, -(self.front_z() + 1.0)),
+            &Vector3::new(0.0, 0.0, 1.0),
+        );
+        let (principal_front, focal_front) =
+            cardinal_points(&scene_ray, &self.trace_from_scene(&scene_ray)?);
+
+        let film_ray = Ray::new(
+            &Point3::new(x, 0.0, 1.0 - self.rear_z()),
+            &Vector3::new(0.0, 0.0, -1.0),
+        );
+        let (principal_rear, _) = cardinal_points(&film_ray, &self.trace_from_film(&film_ray)?);
+
+        // Solve the thick lens equation for how far to move the lens from the film
+        let focal_length = focal_front - principal_front;
+        let z = -focus_dist;
+        let c = (principal_rear - z - principal_front)
+            * (principal_rear - z - 4.0 * focal_length - principal_front);
+        if c <= 0.0 {
+            return None;
+        }
+        let delta = 0.5 * (principal_rear - z + principal_front - c.sqrt());
+
+        Some(self.rear_z() + delta)
+    }
+
+    fn bound_exit_pupil(&self, film_x0: f64, film_x1: f64) -> PupilBounds {
+        // Bounds the points on the rear element's plane that rays from film points between
+        // 'film_x0' and 'film_x1' on the x axis pass through on their way out of the lens
+        let rear_radius = 1.5 * self.interfaces.last().unwrap().aperture_radius;
+        let rear_bounds = PupilBounds {
+            min: (-rear_radius, -rear_radius),
+            max: (rear_radius, rear_radius),
+        };
+
+        let mut bounds: Option<PupilBounds> = None;
+        for i in 0..PUPIL_SAMPLES {
+            let fraction = (i as f64 + 0.5) / PUPIL_SAMPLES as f64;
+            let p_film = Point3::new(film_x0 + fraction * (film_x1 - film_x0), 0.0, 0.0);
+            let p_rear = Point3::new(
+                utils::random_number_in_range(-rear_radius, rear_radius),
+                utils::random_number_in_range(-rear_radius, rear_radius),
+                -self.rear_z(),
+            );
+
+            // Skip tracing points already inside the bounds
+            let inside = bounds.is_some_and(|b| b.contains(p_rear.x, p_rear.y));
+            if inside
+                || self
+                    .trace_from_film(&Ray::new(&p_film, &(&p_rear - &p_film)))
+                    .is_some()
+            {
+                bounds = Some(match bounds {
+                    Some(b) => b.union(p_rear.x, p_rear.y),
+                    None => PupilBounds {
+                        min: (p_rear.x, p_rear.y),
+                        max: (p_rear.x, p_rear.y),
+                    },
+                });
+            }
+        }
+
+        // Pad the bounds by about the spacing between samples, to cover what they missed
+        match bounds {
+            Some(b) => {
+                let diagonal = f64::sqrt(2.0) * 2.0 * rear_radius;
+                let pad = 2.0 * diagonal / (PUPIL_SAMPLES as f64).sqrt();
+                PupilBounds {
+                    min: (b.min.0 - pad, b.min.1 - pad),
+                    max: (b.max.0 + pad, b.max.1 + pad),
+                }
+            }
+            None => rear_bounds,
+        }
+    }
+
+    fn sample_exit_pupil(&self, film_x: f64, film_y: f64, u: (f64, f64)) -> (Point3, f64) {
+        // Returns a point on the rear element's plane within the exit pupil of the film point,
+        // and the area of the bounds it was drawn from
+        let r_film = f64::hypot(film_x, film_y);
+        let band =
+            ((r_film / self.half_diagonal() * PUPIL_BANDS as f64) as usize).min(PUPIL_BANDS - 1);
+        let bounds = self.exit_pupils[band];
+
+        let x = bounds.min.0 + u.0 * (bounds.max.0 - bounds.min.0);
+        let y = bounds.min.1 + u.1 * (bounds.max.1 - bounds.min.1);
+
+        // The bounds were found along the x axis, so turn them to the film point's direction
+        let (sin_theta, cos_theta) = if r_film > 0.0 {
+            (film_y / r_film, film_x / r_film)
+        } else {
+            (0.0, 1.0)
+        };
+        let p_rear = Point3::new(
+            cos_theta * x - sin_theta * y,
+            sin_theta * x + cos_theta * y,
+            -self.rear_z(),
+        );
+
+        (p_rear, bounds.area())
+    }
+}
+
+fn intersect_interface(
+    interface: &LensInterface,
+    z: f64,
+    origin: &Point3,
+    direction: &Vector3,
+) -> Option<(f64, Option<Vector3>)> {
+    // Returns the distance along the ray to the interface at 'z' on the axis, and the surface
+    // normal facing the ray unless it is the flat aperture stop
+    if interface.is_stop() {
+        let t = (z - origin.z) / direction.z;
+        return (t >= 0.0).then_some((t, None));
+    }
+
+    // Sphere centered on the axis, with the interface at its vertex
+    let radius = interface.curvature_radius;
+    let oc = origin - &Point3::new(0.0, 0.0, z + radius);
+    let a = direction.length_squared();
+    let h = Vector3::dot(direction, &oc);
+    let c = oc.length_squared() - radius * radius;
+    let discriminant = h * h - a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+    let sqrtd = discriminant.sqrt();
+    let (t0, t1) = ((-h - sqrtd) / a, (-h + sqrtd) / a);
+
+    // Only one side of the sphere is part of the lens, depending on which way it curves
+    let t = if (direction.z > 0.0) != (radius < 0.0) {
+        t0
+    } else {
+        t1
+    };
+    if t < 0.0 {
+        return None;
+    }
+
+    let normal = Vector3::unit_vector(&(&oc + &(t * direction)));
+    if Vector3::dot(&normal, direction) > 0.0 {
+        Some((t, Some(-normal)))
+    } else {
+        Some((t, Some(normal)))
+    }
+}
+
+fn refract(direction: &Vector3, normal: &Vector3, eta: f64) -> Option<Vector3> {
+    // Snell's law for 'normal' facing against 'direction', with 'eta' the ratio of the incident
+    // over the transmitted index of refraction. Total internal reflection returns None
+    let d = Vector3::unit_vector(direction);
+    let cos_i = -Vector3::dot(&d, normal);
+    let sin2_t = eta * eta * (1.0 - cos_i * cos_i).max(0.0);
+    if sin2_t >= 1.0 {
+        return None;
+    }
+    let cos_t = f64::sqrt(1.0 - sin2_t);
+
+    Some(&(eta * &d) + &((eta * cos_i - cos_t) * normal))
+}
+
+fn cardinal_points(ray_in: &Ray, ray_out: &Ray) -> (f64, f64) {
+    // Returns the z of the principal plane and of the focal point, from a ray parallel to the
+    // axis and the same ray after passing through the lens
+    let (o, d) = (ray_out.origin(), ray_out.direction());
+    let focal_z = o.z + (-o.x / d.x) * d.z;
+    let principal_z = o.z + ((ray_in.origin().x - o.x) / d.x) * d.z;
+
+    (principal_z, focal_z)
+}
+
+fn invalid_data(line: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("invalid lens prescription line '{line}'"),
+    )
+}
@@ -0,0 +1,159 @@
+use crate::{
+    color::Color,
+    onb::Onb,
+    utils,
+    vector3::{Point3, Vector3},
+};
+
+pub struct LightSample {
+    // Unit direction from the shaded point towards the light
+    pub direction: Vector3,
+    pub distance: f64,
+    // Incident radiance at the shaded point, falloff included
+    pub radiance: Color,
+}
+
+pub trait Light {
+    // 'u' spreads the samples over lights that have an area or angle
+    fn sample(&self, p: &Point3, u: (f64, f64)) -> Option<LightSample>;
+}
+
+pub struct PointLight {
+    position: Point3,
+    intensity: Color,
+}
+
+impl PointLight {
+    pub fn new(position: Point3, intensity: Color) -> Self {
+        Self {
+            position,
+            intensity,
+        }
+    }
+}
+
+impl Light for PointLight {
+    fn sample(&self, p: &Point3, _u: (f64, f64)) -> Option<LightSample> {
+        let to_light = &self.position - p;
+        let distance_squared = to_light.length_squared();
+        if distance_squared <= 0.0 {
+            return None;
+        }
+        let distance = distance_squared.sqrt();
+
+        Some(LightSample {
+            direction: &to_light / distance,
+            distance,
+            radiance: &self.intensity / distance_squared,
+        })
+    }
+}
+
+pub struct SpotLight {
+    position: Point3,
+    direction: Vector3,
+    intensity: Color,
+    cos_inner: f64,
+    cos_outer: f64,
+}
+
+impl SpotLight {
+    // Cone angles are given in degrees, measured from the spot axis
+    pub fn new(
+        position: Point3,
+        lookat: Point3,
+        intensity: Color,
+        inner_angle: f64,
+        outer_angle: f64,
+    ) -> Self {
+        let outer_angle = outer_angle.max(inner_angle);
+        Self {
+            position,
+            direction: Vector3::unit_vector(&(&lookat - &position)),
+            intensity,
+            cos_inner: f64::cos(utils::degrees_to_radians(inner_angle)),
+            cos_outer: f64::cos(utils::degrees_to_radians(outer_angle)),
+        }
+    }
+
+    fn falloff(&self, cos_theta: f64) -> f64 {
+        if cos_theta >= self.cos_inner {
+            return 1.0;
+        }
+        if cos_theta <= self.cos_outer {
+            return 0.0;
+        }
+
+        // Smoothstep between the outer and inner cone
+        let t = (cos_theta - self.cos_outer) / (self.cos_inner - self.cos_outer);
+        t * t * (3.0 - 2.0 * t)
+    }
+}
+
+impl Light for SpotLight {
+    fn sample(&self, p: &Point3, _u: (f64, f64)) -> Option<LightSample> {
+        let to_light = &self.position - p;
+        let distance_squared = to_light.length_squared();
+        if distance_squared <= 0.0 {
+            return None;
+        }
+        let distance = distance_squared.sqrt();
+        let direction = &to_light / distance;
+
+        let falloff = self.falloff(Vector3::dot(&-direction, &self.direction));
+        if falloff <= 0.0 {
+            return None;
+        }
+
+        Some(LightSample {
+            direction,
+            distance,
+            radiance: &self.intensity * (falloff / distance_squared),
+        })
+    }
+}
+
+pub struct DirectionalLight {
+    // Unit direction pointing from the scene towards the light
+    to_light: Vector3,
+    irradiance: Color,
+    cos_half_angle: f64,
+}
+
+impl DirectionalLight {
+    // 'direction' is where the light travels to; an angular diameter above zero, in degrees,
+    // spreads the light over a cone and produces soft shadows
+    pub fn new(direction: Vector3, irradiance: Color, angular_diameter: f64) -> Self {
+        let half_angle = utils::degrees_to_radians(angular_diameter.max(0.0) / 2.0);
+        Self {
+            to_light: -Vector3::unit_vector(&direction),
+            irradiance,
+            cos_half_angle: f64::cos(half_angle),
+        }
+    }
+}
+
+impl Light for DirectionalLight {
+    fn sample(&self, _p: &Point3, u: (f64, f64)) -> Option<LightSample> {
+        let direction = if self.cos_half_angle < 1.0 {
+            // Uniformly sample the cone subtended by the light
+            let cos_theta = 1.0 - u.0 * (1.0 - self.cos_half_angle);
+            let sin_theta = f64::sqrt(1.0 - cos_theta * cos_theta);
+            let phi = 2.0 * std::f64::consts::PI * u.1;
+            let local = Vector3::new(
+                f64::cos(phi) * sin_theta,
+                f64::sin(phi) * sin_theta,
+                cos_theta,
+            );
+            Onb::new(&self.to_light).transform(&local)
+        } else {
+            self.to_light
+        };
+
+        Some(LightSample {
+            direction,
+            distance: f64::INFINITY,
+            radiance: self.irradiance,
+        })
+    }
+}
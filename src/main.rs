@@ -1,27 +1,445 @@
 use {
-    camera::Camera,
+    aperture::Aperture,
+    camera::{
+        AdaptiveSampling, Camera, FocusTarget, Projection, Stereo, StereoLayout, ThinLens,
+        TiltShift,
+    },
     color::Color,
+    environment::{EnvironmentMap, Gradient},
+    filter::Filter,
     hittable_list::HittableList,
-    material::{Dielectric, Lambertian, Material, Metal},
+    image::Image,
+    lens_system::LensSystem,
+    light::{DirectionalLight, PointLight, SpotLight},
+    material::{
+        AlphaMask, Conductor, Dielectric, Lambertian, LayeredMaterial, Material, Metal,
+        MixMaterial, OrenNayar, RoughDielectric,
+    },
+    normal_map::{BumpMap, NormalMap},
+    principled::Principled,
+    quad::{make_box, Quad},
+    sampler::Sampling,
+    scene::Scene,
+    sky::Sky,
+    spectrum::Dispersion,
     sphere::Sphere,
+    texture::{CheckerTexture, ImageTexture, NoiseTexture, SolidColor},
+    thin_film::ThinFilm,
+    triangle::Triangle,
     vector3::{Point3, Vector3},
 };
 
 use std::rc::Rc;
 
+mod aperture;
 mod camera;
 mod color;
+mod distribution;
+mod environment;
+mod filter;
 mod hittable;
 mod hittable_list;
+mod image;
 mod interval;
+mod lens_system;
+mod light;
 mod material;
+mod microfacet;
+mod normal_map;
+mod onb;
+mod pdf;
+mod perlin;
+mod principled;
+mod quad;
 mod ray;
+mod sampler;
+mod scene;
+mod sky;
+mod spectrum;
 mod sphere;
+mod texture;
+mod thin_film;
+mod triangle;
 mod utils;
 mod vector3;
 
 fn main() -> std::io::Result<()> {
-    // World
+    let args: Vec<String> = std::env::args().collect();
+    let scene = args.get(1).map(String::as_str).unwrap_or_default();
+
+    match scene {
+        "lights" => lit_spheres(),
+        "glass" => showcase(vec![
+            Rc::new(Dielectric::new(1.5)),
+            Rc::new(Dielectric::new(1.5).with_exact_fresnel()),
+            Rc::new(RoughDielectric::new(1.5, 0.1)),
+            Rc::new(RoughDielectric::new(1.5, 0.3)),
+            Rc::new(RoughDielectric::new(1.5, 0.6)),
+        ]),
+        "tinted" => showcase(vec![
+            Rc::new(Dielectric::new(1.5).with_absorption(Color::new(0.1, 0.6, 0.9))),
+            Rc::new(Dielectric::new(1.33).with_absorption(Color::new(0.45, 0.06, 0.015))),
+            Rc::new(Dielectric::new(2.42).with_absorption(Color::new(1.2, 1.2, 0.1))),
+            Rc::new(RoughDielectric::new(1.5, 0.25).with_absorption(Color::new(0.9, 0.2, 0.9))),
+        ]),
+        "dispersion" => spectral_showcase(vec![
+            Rc::new(Dielectric::new(1.5).with_dispersion(Dispersion::bk7())),
+            Rc::new(
+                Dielectric::new(1.5).with_dispersion(Dispersion::Cauchy { a: 1.67, b: 0.0221 }),
+            ),
+            Rc::new(Dielectric::new(1.5).with_dispersion(Dispersion::diamond())),
+        ]),
+        "metals" => showcase(vec![
+            Rc::new(Conductor::gold(0.0)),
+            Rc::new(Conductor::copper(0.3)),
+            Rc::new(Conductor::aluminium(0.2)),
+            Rc::new(Conductor::anisotropic(
+                Color::new(1.657, 0.880, 0.521),
+                Color::new(9.224, 6.270, 4.837),
+                0.05,
+                0.5,
+            )),
+            Rc::new(Conductor::silver(0.15)),
+        ]),
+        "diffuse" => showcase(vec![
+            Rc::new(Lambertian::new(Color::new(0.7, 0.45, 0.3))),
+            Rc::new(OrenNayar::new(Color::new(0.7, 0.45, 0.3), 20.0)),
+            Rc::new(OrenNayar::new(Color::new(0.7, 0.45, 0.3), 40.0)),
+            Rc::new(OrenNayar::new(Color::new(0.7, 0.45, 0.3), 60.0)),
+        ]),
+        "films" => showcase(vec![
+            Rc::new(Dielectric::new(1.0).with_thin_film(ThinFilm::new(380.0, 1.33))),
+            Rc::new(Dielectric::new(1.5).with_thin_film(ThinFilm::new(100.0, 1.38))),
+            Rc::new(
+                Conductor::new(
+                    Color::new(2.87, 2.95, 2.65),
+                    Color::new(3.35, 2.93, 2.81),
+                    0.0,
+                )
+                .with_thin_film(ThinFilm::new(110.0, 2.4)),
+            ),
+            Rc::new(
+                Conductor::new(
+                    Color::new(2.87, 2.95, 2.65),
+                    Color::new(3.35, 2.93, 2.81),
+                    0.2,
+                )
+                .with_thin_film(ThinFilm::new(60.0, 2.4)),
+            ),
+        ]),
+        "layered" => showcase(vec![
+            Rc::new(LayeredMaterial::new(
+                Rc::new(MixMaterial::new(
+                    Rc::new(Lambertian::new(Color::new(0.6, 0.02, 0.02))),
+                    Rc::new(Conductor::aluminium(0.35)),
+                    SolidColor::scalar(0.3),
+                )),
+                1.5,
+            )),
+            Rc::new(
+                LayeredMaterial::new(
+                    Rc::new(
+                        Principled::new(CheckerTexture::from_colors(
+                            0.2,
+                            Color::new(0.5, 0.3, 0.15),
+                            Color::new(0.35, 0.2, 0.1),
+                        ))
+                        .with_roughness(SolidColor::scalar(1.0)),
+                    ),
+                    1.5,
+                )
+                .with_tint(Color::new(0.9, 0.75, 0.5)),
+            ),
+            Rc::new(MixMaterial::new(
+                Rc::new(Conductor::gold(0.1)),
+                Rc::new(OrenNayar::new(Color::new(0.8, 0.8, 0.8), 30.0)),
+                CheckerTexture::from_colors(
+                    0.3,
+                    Color::new(0.0, 0.0, 0.0),
+                    Color::new(1.0, 1.0, 1.0),
+                ),
+            )),
+            Rc::new(LayeredMaterial::new(
+                Rc::new(Lambertian::new(Color::new(0.1, 0.2, 0.5))),
+                1.5,
+            )),
+        ]),
+        "principled" => showcase(vec![
+            Rc::new(
+                Principled::new(SolidColor::new(Color::new(0.8, 0.1, 0.1)))
+                    .with_roughness(SolidColor::scalar(0.4))
+                    .with_clearcoat(SolidColor::scalar(1.0))
+                    .with_clearcoat_gloss(SolidColor::scalar(0.9)),
+            ),
+            Rc::new(
+                Principled::new(SolidColor::new(Color::new(1.0, 0.78, 0.34)))
+                    .with_metallic(SolidColor::scalar(1.0))
+                    .with_roughness(SolidColor::scalar(0.3)),
+            ),
+            Rc::new(
+                Principled::new(SolidColor::new(Color::new(0.3, 0.1, 0.4)))
+                    .with_roughness(SolidColor::scalar(1.0))
+                    .with_sheen(SolidColor::scalar(1.0)),
+            ),
+            Rc::new(
+                Principled::new(SolidColor::new(Color::new(0.95, 1.0, 0.97)))
+                    .with_roughness(SolidColor::scalar(0.1))
+                    .with_transmission(SolidColor::scalar(1.0))
+                    .with_ior(SolidColor::scalar(1.5)),
+            ),
+            Rc::new(
+                Principled::new(SolidColor::new(Color::new(0.1, 0.3, 0.8)))
+                    .with_roughness(CheckerTexture::from_colors(
+                        0.25,
+                        Color::new(0.15, 0.15, 0.15),
+                        Color::new(0.7, 0.7, 0.7),
+                    ))
+                    .with_specular(SolidColor::scalar(1.0))
+                    .with_specular_tint(SolidColor::scalar(0.5)),
+            ),
+        ]),
+        "projection" => match args.get(2).map(String::as_str) {
+            Some("orthographic") => projected_spheres(Projection::Orthographic { height: 6.0 }),
+            Some("fisheye") => projected_spheres(Projection::Fisheye { fov: 180.0 }),
+            Some("equirectangular") => projected_spheres(Projection::Equirectangular),
+            _ => {
+                eprintln!("usage: ray-tracer projection <orthographic|fisheye|equirectangular>");
+                Ok(())
+            }
+        },
+        "stereo" => {
+            let projection = match args.get(2).map(String::as_str) {
+                Some("perspective") => Projection::Perspective,
+                Some("equirectangular") => Projection::Equirectangular,
+                _ => {
+                    eprintln!(
+                        "usage: ray-tracer stereo <perspective|equirectangular> \
+                         [side-by-side|over-under|separate]"
+                    );
+                    return Ok(());
+                }
+            };
+            let layout = match args.get(3).map(String::as_str) {
+                Some("side-by-side") => StereoLayout::SideBySide,
+                Some("over-under") => StereoLayout::OverUnder,
+                Some("separate") => StereoLayout::Separate,
+                // Panoramas are usually packed over-under to keep the frame from getting too wide
+                _ => match projection {
+                    Projection::Equirectangular => StereoLayout::OverUnder,
+                    _ => StereoLayout::SideBySide,
+                },
+            };
+            stereo_spheres(projection, layout)
+        }
+        "sampling" => {
+            let sampling = match args.get(2).map(String::as_str) {
+                Some("independent") => Sampling::Independent,
+                Some("stratified") => Sampling::Stratified,
+                Some("halton") => Sampling::Halton,
+                Some("sobol") => Sampling::Sobol,
+                Some("cmj") => Sampling::CorrelatedMultiJittered,
+                Some("blue-noise") => Sampling::BlueNoise,
+                _ => {
+                    eprintln!(
+                        "usage: ray-tracer sampling <independent|stratified|halton|sobol|cmj|blue-noise>"
+                    );
+                    return Ok(());
+                }
+            };
+            sampled_spheres(sampling)
+        }
+        "adaptive" => adaptive_spheres(),
+        "filter" => {
+            let filter = match args.get(2).map(String::as_str) {
+                Some("box") => Filter::Box { radius: 0.5 },
+                Some("gaussian") => Filter::Gaussian {
+                    radius: 1.5,
+                    sigma: 0.5,
+                },
+                Some("mitchell") => Filter::Mitchell {
+                    radius: 2.0,
+                    b: 1.0 / 3.0,
+                    c: 1.0 / 3.0,
+                },
+                Some("lanczos") => Filter::Lanczos { radius: 3.0 },
+                Some("blackman-harris") => Filter::BlackmanHarris { radius: 1.5 },
+                _ => {
+                    eprintln!(
+                        "usage: ray-tracer filter <box|gaussian|mitchell|lanczos|blackman-harris>"
+                    );
+                    return Ok(());
+                }
+            };
+            filtered_spheres(filter)
+        }
+        "sky" => {
+            let elevation = args.get(2).and_then(|a| a.parse().ok()).unwrap_or(45.0);
+            sky_spheres(elevation)
+        }
+        "tilt-shift" => match args.get(2).map(String::as_str) {
+            // Looking straight ahead at the buildings, with the lens shifted up to their tops
+            Some("shift") => city_blocks(
+                Point3::new(0.0, 1.7, 24.0),
+                Point3::new(0.0, 1.7, 0.0),
+                0.0,
+                TiltShift {
+                    shift_vertical: 0.35,
+                    ..Default::default()
+                },
+            ),
+            // Looking down on the city, with the plane of focus tilted against the ground so
+            // that only a narrow band stays sharp, which makes it look like a miniature
+            Some("tilt") => city_blocks(
+                Point3::new(26.0, 22.0, 26.0),
+                Point3::new(0.0, 0.0, 0.0),
+                4.0,
+                TiltShift {
+                    tilt: -30.0,
+                    ..Default::default()
+                },
+            ),
+            _ => {
+                eprintln!("usage: ray-tracer tilt-shift <shift|tilt>");
+                Ok(())
+            }
+        },
+        "lens" => {
+            let path = args
+                .get(2)
+                .map(String::as_str)
+                .unwrap_or("lenses/double_gauss_50mm.dat");
+            let mut lens_system = LensSystem::load(path)?;
+            if let Some(stop) = args.get(3).and_then(|a| a.parse().ok()) {
+                lens_system = lens_system.with_stop(stop);
+            }
+            lens_spheres(lens_system)
+        }
+        "bokeh" => match args.get(2) {
+            None => bokeh_spheres(Aperture::Circular),
+            Some(arg) => match arg.parse() {
+                Ok(blades) => bokeh_spheres(Aperture::Polygonal {
+                    blades,
+                    rotation: 15.0,
+                }),
+                Err(_) => bokeh_spheres(Aperture::load(arg)?),
+            },
+        },
+        "focus" => {
+            let coordinates: Vec<i32> = args[2..].iter().filter_map(|a| a.parse().ok()).collect();
+            match coordinates[..] {
+                [i, j] => focused_spheres(FocusTarget::Pixel(i, j)),
+                _ => focused_spheres(FocusTarget::Lookat),
+            }
+        }
+        "cutout" => cutout_shapes(),
+        "mapped" => mapped_surfaces(args.get(2).map(String::as_str)),
+        "hdri" => match args.get(2) {
+            Some(path) => environment_lit_spheres(path),
+            None => {
+                eprintln!("usage: ray-tracer hdri <equirectangular .hdr image>");
+                Ok(())
+            }
+        },
+        _ => bouncing_spheres(),
+    }
+}
+
+fn bouncing_spheres() -> std::io::Result<()> {
+    let mut cam = book_camera();
+    cam.render(&Scene::new(random_spheres()))
+}
+
+fn sampled_spheres(sampling: Sampling) -> std::io::Result<()> {
+    // Few samples per pixel, where the sampling pattern makes the most difference
+    let mut cam = book_camera();
+    cam.image_width = 600;
+    cam.samples_per_pixel = 16;
+    cam.sampling = sampling;
+    cam.render(&Scene::new(random_spheres()))
+}
+
+fn adaptive_spheres() -> std::io::Result<()> {
+    let mut cam = book_camera();
+    cam.image_width = 600;
+    cam.sampling = Sampling::Sobol;
+    cam.adaptive = Some(AdaptiveSampling {
+        min_samples: 16,
+        max_samples: 128,
+        threshold: 0.1,
+        heatmap: true,
+    });
+    cam.render(&Scene::new(random_spheres()))
+}
+
+fn filtered_spheres(filter: Filter) -> std::io::Result<()> {
+    let mut cam = book_camera();
+    cam.image_width = 600;
+    cam.samples_per_pixel = 32;
+    cam.sampling = Sampling::Sobol;
+    cam.filter = filter;
+    cam.render(&Scene::new(random_spheres()))
+}
+
+fn sky_spheres(sun_elevation: f64) -> std::io::Result<()> {
+    let mut scene = Scene::new(random_spheres());
+
+    add_sky(&mut scene, sun_elevation, 120.0);
+
+    let mut cam = book_camera();
+    cam.render(&scene)
+}
+
+fn projected_spheres(projection: Projection) -> std::io::Result<()> {
+    let mut scene = Scene::new(random_spheres());
+
+    add_sky(&mut scene, 40.0, 120.0);
+
+    let mut cam = book_camera();
+    cam.image_width = 600;
+    cam.projection = projection;
+    cam.defocus_angle = 0.0;
+
+    match projection {
+        // Orthographic rays start on the camera plane, which must clear the ground
+        Projection::Orthographic { .. } => cam.lookfrom = Point3::new(13.0, 5.0, 3.0),
+        // Panoramas look around from within the spheres
+        Projection::Equirectangular => {
+            cam.aspect_ratio = 2.0;
+            cam.lookfrom = Point3::new(6.0, 1.0, 1.5);
+            cam.lookat = Point3::new(0.0, 1.0, 0.0);
+        }
+        _ => {}
+    }
+
+    cam.render(&scene)
+}
+
+fn stereo_spheres(projection: Projection, layout: StereoLayout) -> std::io::Result<()> {
+    let mut scene = Scene::new(random_spheres());
+
+    add_sky(&mut scene, 40.0, 120.0);
+
+    let mut cam = book_camera();
+    cam.image_width = 600;
+    cam.projection = projection;
+    cam.defocus_angle = 0.0;
+    // The book scene is about 20 units wide, so a wide baseline keeps the depth visible
+    cam.stereo = Some(Stereo {
+        interpupillary_distance: 0.2,
+        convergence: 10.0,
+        layout,
+    });
+
+    if let Projection::Equirectangular = projection {
+        cam.aspect_ratio = 2.0;
+        cam.lookfrom = Point3::new(6.0, 1.0, 1.5);
+        cam.lookat = Point3::new(0.0, 1.0, 0.0);
+    }
+
+    cam.render(&scene)
+}
+
+fn random_spheres() -> HittableList {
     let mut world = HittableList::zero();
 
     let material_ground = Lambertian::new(Color::new(0.5, 0.5, 0.5));
@@ -69,6 +487,167 @@ fn main() -> std::io::Result<()> {
     let material3 = Rc::new(Metal::new(Color::new(0.7, 0.6, 0.5), 0.0));
     world.add(Sphere::new(Point3::new(4.0, 1.0, 0.0), 1.0, material3));
 
+    world
+}
+
+fn city_blocks(
+    lookfrom: Point3,
+    lookat: Point3,
+    defocus_angle: f64,
+    tilt_shift: TiltShift,
+) -> std::io::Result<()> {
+    let mut world = HittableList::zero();
+
+    let material_ground = Rc::new(Lambertian::new(Color::new(0.4, 0.4, 0.4)));
+    world.add(Quad::new(
+        Point3::new(-100.0, 0.0, 100.0),
+        Vector3::new(200.0, 0.0, 0.0),
+        Vector3::new(0.0, 0.0, -200.0),
+        material_ground,
+    ));
+
+    // A grid of blocks with random heights and colors
+    for a in -3..=3 {
+        for b in -3..=3 {
+            let albedo = &Color::random() * &Color::random();
+            let height = 2.0 + 10.0 * rand::random::<f64>();
+            let corner = Point3::new(3.5 * a as f64 - 1.0, 0.0, 3.5 * b as f64 - 1.0);
+            world.add(make_box(
+                &corner,
+                &(&corner + &Vector3::new(2.0, height, 2.0)),
+                Rc::new(Lambertian::new(albedo)),
+            ));
+        }
+    }
+
+    let mut scene = Scene::new(world);
+
+    add_sky(&mut scene, 50.0, 130.0);
+
+    let mut cam = Camera::new();
+
+    cam.aspect_ratio = 16.0 / 9.0;
+    cam.image_width = 600;
+    cam.samples_per_pixel = 50;
+    cam.max_depth = 20;
+
+    cam.vfov = 40.0;
+    cam.lookfrom = lookfrom;
+    cam.lookat = lookat;
+    cam.vup = Vector3::new(0.0, 1.0, 0.0);
+
+    cam.defocus_angle = defocus_angle;
+    cam.focus_dist = (&lookfrom - &lookat).length();
+    cam.tilt_shift = tilt_shift;
+
+    cam.render(&scene)
+}
+
+fn focused_spheres(target: FocusTarget) -> std::io::Result<()> {
+    let mut scene = Scene::new(random_spheres());
+
+    add_sky(&mut scene, 40.0, 120.0);
+
+    let mut cam = book_camera();
+    cam.image_width = 600;
+    cam.samples_per_pixel = 50;
+
+    if cam.focus_on(&scene, target).is_none() {
+        println!("Nothing to focus on, keeping the focus distance");
+    }
+    cam.defocus_angle = cam.defocus_angle_for_f_number(1.4, 36.0);
+
+    let (near, far) = cam.depth_of_field(2.0);
+    println!(
+        "Focused at {:.2}, sharp from {:.2} to {:.2}",
+        cam.focus_dist, near, far
+    );
+
+    cam.render(&scene)
+}
+
+fn lens_spheres(lens_system: LensSystem) -> std::io::Result<()> {
+    let mut scene = Scene::new(random_spheres());
+
+    add_sky(&mut scene, 40.0, 120.0);
+
+    let mut cam = book_camera();
+    cam.image_width = 600;
+    cam.samples_per_pixel = 50;
+    cam.lens_system = Some(lens_system);
+
+    cam.render(&scene)
+}
+
+fn bokeh_spheres(aperture: Aperture) -> std::io::Result<()> {
+    let mut world = HittableList::zero();
+
+    let material_ground = Rc::new(Lambertian::new(Color::new(0.1, 0.1, 0.1)));
+    world.add(Sphere::new(
+        Point3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        material_ground,
+    ));
+
+    // Something to focus on in front of the field
+    let material_focus = Rc::new(Lambertian::new(Color::new(0.7, 0.2, 0.1)));
+    world.add(Sphere::new(
+        Point3::new(11.0, 0.15, 2.5),
+        0.15,
+        material_focus,
+    ));
+
+    // Polished beads in the background, whose small highlights blur into the bokeh
+    let material_bead = Rc::new(Conductor::silver(0.2));
+    for _ in 0..80 {
+        let center = Point3::new(
+            16.0 * rand::random::<f64>() - 10.0,
+            0.15,
+            12.0 * rand::random::<f64>() - 6.0,
+        );
+        world.add(Sphere::new(center, 0.15, material_bead.clone()));
+    }
+
+    // A night scene, so the highlights stand out
+    let mut scene = Scene::new(world);
+    scene.set_environment(Gradient::new(
+        Color::new(0.02, 0.02, 0.03),
+        Color::new(0.01, 0.02, 0.06),
+    ));
+
+    scene.add_light(PointLight::new(
+        Point3::new(11.0, 3.0, 4.0),
+        Color::new(10.0, 9.0, 8.0),
+    ));
+    scene.add_light(PointLight::new(
+        Point3::new(-2.0, 2.0, -3.0),
+        Color::new(40.0, 25.0, 10.0),
+    ));
+    scene.add_light(PointLight::new(
+        Point3::new(2.0, 1.5, 4.0),
+        Color::new(10.0, 20.0, 40.0),
+    ));
+
+    let mut cam = book_camera();
+    cam.image_width = 600;
+    cam.samples_per_pixel = 100;
+    cam.lookfrom = Point3::new(13.0, 0.4, 3.0);
+    cam.lookat = Point3::new(11.0, 0.3, 2.5);
+    cam.focus_dist = (&cam.lookfrom - &cam.lookat).length();
+
+    // A fast portrait lens on a full frame sensor
+    cam.lens = Some(ThinLens {
+        f_number: 1.4,
+        focal_length: 85.0,
+        sensor_width: 36.0,
+    });
+    cam.aperture = aperture;
+    cam.cat_eye = 0.5;
+
+    cam.render(&scene)
+}
+
+fn book_camera() -> Camera {
     let mut cam = Camera::new();
 
     cam.aspect_ratio = 16.0 / 9.0;
@@ -84,6 +663,273 @@ fn main() -> std::io::Result<()> {
     cam.defocus_angle = 0.6;
     cam.focus_dist = 10.0;
 
-    cam.render(&world)?;
-    Ok(())
+    cam
+}
+
+fn lit_spheres() -> std::io::Result<()> {
+    // World
+    let mut world = HittableList::zero();
+
+    let material_ground = Rc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+    world.add(Sphere::new(
+        Point3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        material_ground,
+    ));
+
+    let material_left = Rc::new(Lambertian::new(Color::new(0.4, 0.2, 0.1)));
+    world.add(Sphere::new(Point3::new(-2.2, 1.0, 0.0), 1.0, material_left));
+
+    let material_center = Rc::new(Dielectric::new(1.5));
+    world.add(Sphere::new(
+        Point3::new(0.0, 1.0, 0.0),
+        1.0,
+        material_center,
+    ));
+
+    let material_right = Rc::new(Metal::new(Color::new(0.7, 0.6, 0.5), 0.1));
+    world.add(Sphere::new(Point3::new(2.2, 1.0, 0.0), 1.0, material_right));
+
+    // Lights
+    let mut scene = Scene::new(world);
+
+    scene.add_light(PointLight::new(
+        Point3::new(-3.0, 3.0, 3.0),
+        Color::new(10.0, 9.0, 8.0),
+    ));
+    scene.add_light(SpotLight::new(
+        Point3::new(3.0, 5.0, 2.0),
+        Point3::new(2.2, 0.0, 0.0),
+        Color::new(40.0, 40.0, 60.0),
+        15.0,
+        25.0,
+    ));
+    scene.add_light(DirectionalLight::new(
+        Vector3::new(-1.0, -2.0, -1.0),
+        Color::new(0.6, 0.55, 0.5),
+        2.0,
+    ));
+
+    let mut cam = row_camera(30.0);
+
+    cam.render(&scene)
+}
+
+fn environment_lit_spheres(path: &str) -> std::io::Result<()> {
+    // World
+    let mut world = HittableList::zero();
+
+    let material_ground = Rc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+    world.add(Sphere::new(
+        Point3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        material_ground,
+    ));
+
+    let material_left = Rc::new(Lambertian::new(Color::new(0.8, 0.3, 0.2)));
+    world.add(Sphere::new(Point3::new(-2.2, 1.0, 0.0), 1.0, material_left));
+
+    let material_center = Rc::new(Dielectric::new(1.5));
+    world.add(Sphere::new(
+        Point3::new(0.0, 1.0, 0.0),
+        1.0,
+        material_center,
+    ));
+
+    let material_right = Rc::new(Metal::new(Color::new(0.8, 0.8, 0.8), 0.0));
+    world.add(Sphere::new(Point3::new(2.2, 1.0, 0.0), 1.0, material_right));
+
+    // Environment
+    let mut scene = Scene::new(world);
+    scene.set_environment(EnvironmentMap::load(path, 1.0)?);
+
+    let mut cam = row_camera(30.0);
+
+    cam.render(&scene)
+}
+
+fn mapped_surfaces(normal_map: Option<&str>) -> std::io::Result<()> {
+    // World
+    let mut world = HittableList::zero();
+
+    let material_ground = Rc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+    world.add(Quad::new(
+        Point3::new(-20.0, 0.0, 20.0),
+        Vector3::new(40.0, 0.0, 0.0),
+        Vector3::new(0.0, 0.0, -40.0),
+        material_ground,
+    ));
+
+    let material_bumpy = Rc::new(BumpMap::new(
+        Rc::new(Lambertian::new(Color::new(0.8, 0.8, 0.8))),
+        NoiseTexture::new(4.0),
+        0.02,
+    ));
+    world.add(Sphere::new(
+        Point3::new(-3.3, 1.0, 0.0),
+        1.0,
+        material_bumpy,
+    ));
+
+    let material_hammered = Rc::new(BumpMap::new(
+        Rc::new(Conductor::copper(0.15)),
+        NoiseTexture::new(8.0),
+        0.03,
+    ));
+    world.add(Sphere::new(
+        Point3::new(-1.1, 1.0, 0.0),
+        1.0,
+        material_hammered,
+    ));
+
+    // Brushed along the sphere's tangents, so the highlight stretches around its poles
+    let material_brushed = Rc::new(Conductor::anisotropic(
+        Color::new(1.657, 0.880, 0.521),
+        Color::new(9.224, 6.270, 4.837),
+        0.05,
+        0.4,
+    ));
+    world.add(Sphere::new(
+        Point3::new(1.1, 1.0, 0.0),
+        1.0,
+        material_brushed,
+    ));
+
+    // A panel of two triangles sharing one texture, with a normal map if one was given
+    let base = Rc::new(Lambertian::new(Color::new(0.7, 0.6, 0.5)));
+    let material_panel: Rc<dyn Material> = match normal_map {
+        Some(path) => Rc::new(NormalMap::new(
+            base,
+            ImageTexture::new(Image::load_ppm(path)?),
+        )),
+        None => Rc::new(BumpMap::new(base, NoiseTexture::new(10.0), 0.01)),
+    };
+    let corners = [
+        Point3::new(2.4, 0.0, 0.5),
+        Point3::new(4.2, 0.0, -0.5),
+        Point3::new(4.2, 2.0, -0.5),
+        Point3::new(2.4, 2.0, 0.5),
+    ];
+    world.add(
+        Triangle::new(corners[0], corners[1], corners[2], material_panel.clone()).with_uvs([
+            (0.0, 0.0),
+            (1.0, 0.0),
+            (1.0, 1.0),
+        ]),
+    );
+    world.add(
+        Triangle::new(corners[0], corners[2], corners[3], material_panel).with_uvs([
+            (0.0, 0.0),
+            (1.0, 1.0),
+            (0.0, 1.0),
+        ]),
+    );
+
+    // Lights
+    let mut scene = Scene::new(world);
+
+    add_sky(&mut scene, 35.0, 140.0);
+
+    let mut cam = row_camera(40.0);
+
+    cam.render(&scene)
+}
+
+fn cutout_shapes() -> std::io::Result<()> {
+    let (mut scene, mut cam) = showcase_setup(vec![
+        Rc::new(
+            AlphaMask::new(
+                Rc::new(Lambertian::new(Color::new(0.8, 0.4, 0.1))),
+                NoiseTexture::new(3.0),
+            )
+            .with_threshold(0.5),
+        ),
+        Rc::new(AlphaMask::new(
+            Rc::new(Lambertian::new(Color::new(0.1, 0.3, 0.8))),
+            SolidColor::scalar(0.4),
+        )),
+        Rc::new(AlphaMask::new(
+            Rc::new(Conductor::gold(0.2)),
+            CheckerTexture::from_colors(0.25, Color::new(1.0, 1.0, 1.0), Color::new(0.0, 0.0, 0.0)),
+        )),
+    ]);
+
+    // A fence of square holes behind the spheres
+    let material_fence = Rc::new(
+        AlphaMask::new(
+            Rc::new(Lambertian::new(Color::new(0.6, 0.6, 0.55))),
+            CheckerTexture::from_colors(0.3, Color::new(1.0, 1.0, 1.0), Color::new(0.0, 0.0, 0.0)),
+        )
+        .with_threshold(0.5),
+    );
+    scene.world.add(Quad::new(
+        Point3::new(-5.0, 0.0, -2.5),
+        Vector3::new(10.0, 0.0, 0.0),
+        Vector3::new(0.0, 3.0, 0.0),
+        material_fence,
+    ));
+
+    cam.render(&scene)
+}
+
+fn showcase(materials: Vec<Rc<dyn Material>>) -> std::io::Result<()> {
+    let (scene, mut cam) = showcase_setup(materials);
+    cam.render(&scene)
+}
+
+fn spectral_showcase(materials: Vec<Rc<dyn Material>>) -> std::io::Result<()> {
+    let (scene, mut cam) = showcase_setup(materials);
+    cam.spectral = true;
+    cam.samples_per_pixel = 200;
+    cam.render(&scene)
+}
+
+fn showcase_setup(materials: Vec<Rc<dyn Material>>) -> (Scene, Camera) {
+    // A row of spheres, one per material, under a daylight sky
+    let mut world = HittableList::zero();
+
+    let material_ground = Rc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+    world.add(Sphere::new(
+        Point3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        material_ground,
+    ));
+
+    let spacing = 2.2;
+    let first = -spacing * (materials.len() as f64 - 1.0) / 2.0;
+    for (i, material) in materials.into_iter().enumerate() {
+        let center = Point3::new(first + spacing * i as f64, 1.0, 0.0);
+        world.add(Sphere::new(center, 1.0, material));
+    }
+
+    let mut scene = Scene::new(world);
+
+    add_sky(&mut scene, 35.0, 60.0);
+
+    (scene, row_camera(40.0))
+}
+
+fn row_camera(vfov: f64) -> Camera {
+    // Looks down at a row of unit spheres along the x axis, from 10 units in front of them
+    let mut cam = Camera::new();
+
+    cam.aspect_ratio = 16.0 / 9.0;
+    cam.image_width = 400;
+    cam.samples_per_pixel = 50;
+    cam.max_depth = 20;
+
+    cam.vfov = vfov;
+    cam.lookfrom = Point3::new(0.0, 3.0, 10.0);
+    cam.lookat = Point3::new(0.0, 1.0, 0.0);
+    cam.vup = Vector3::new(0.0, 1.0, 0.0);
+
+    cam
+}
+
+fn add_sky(scene: &mut Scene, sun_elevation: f64, sun_azimuth: f64) {
+    // Daylight sky with the sun at the given angles in degrees, which also lights the scene
+    // directly
+    let sky = Sky::new(sun_elevation, sun_azimuth, 3.0, 1.0);
+    scene.add_light(sky.sun_light());
+    scene.set_environment(sky);
 }
@@ -1,24 +1,68 @@
-use crate::{color::Color, hittable::HitRecord, ray::Ray, utils, vector3::Vector3};
+use std::{f64::consts::PI, rc::Rc};
 
-pub trait Material {
-    fn scatter(
-        &self,
-        r_in: &Ray,
-        rec: &HitRecord,
-        attenuation: &mut Color,
-        scattered: &mut Ray,
-    ) -> bool;
+use crate::{
+    color::Color,
+    hittable::HitRecord,
+    microfacet::{self, MicrofacetDielectricPdf, MicrofacetReflectionPdf, TrowbridgeReitz},
+    onb::Onb,
+    pdf::{CosinePdf, MixturePdf, Pdf},
+    ray::Ray,
+    sampler,
+    spectrum::{self, Dispersion},
+    texture::Texture,
+    thin_film::ThinFilm,
+    utils,
+    vector3::Vector3,
+};
+
+pub struct ScatterRecord {
+    pub attenuation: Color,
+    // Specular scatters follow 'specular_ray' directly and are weighted by 'attenuation';
+    // every other scatter samples 'pdf' and is weighted by 'Material::eval'
+    pub is_specular: bool,
+    pub specular_ray: Ray,
+    pub pdf: Option<Box<dyn Pdf>>,
 }
 
-impl dyn Material {
-    fn scatter(
-        &self,
-        r_in: &Ray,
-        rec: &HitRecord,
-        attenuation: &mut Color,
-        scattered: &mut Ray,
-    ) -> bool {
-        false
+impl ScatterRecord {
+    pub fn specular(attenuation: Color, specular_ray: Ray) -> Self {
+        Self {
+            attenuation,
+            is_specular: true,
+            specular_ray,
+            pdf: None,
+        }
+    }
+
+    pub fn diffuse(attenuation: Color, pdf: impl Pdf + 'static) -> Self {
+        Self {
+            attenuation,
+            is_specular: false,
+            specular_ray: Ray::zero(),
+            pdf: Some(Box::new(pdf)),
+        }
+    }
+}
+
+pub trait Material {
+    // 'uc' and 'u' are sample values for any choice or direction the scatter makes itself
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, uc: f64, u: (f64, f64))
+        -> Option<ScatterRecord>;
+
+    // BSDF times the cosine of the scattered direction, for non-specular scatters. It covers
+    // only the scatters that are not specular, divided by their probability
+    fn eval(&self, _r_in: &Ray, _rec: &HitRecord, _scattered: &Ray) -> Color {
+        Color::zero()
+    }
+
+    // Probability that 'scatter' returns a specular record for 'r_in' at the hit
+    fn specular_probability(&self, _r_in: &Ray, _rec: &HitRecord) -> f64 {
+        0.0
+    }
+
+    // Opacity of the surface at the hit, from 0 (cut out) to 1 (solid)
+    fn alpha(&self, _rec: &HitRecord) -> f64 {
+        1.0
     }
 }
 
@@ -35,20 +79,86 @@ impl Lambertian {
 impl Material for Lambertian {
     fn scatter(
         &self,
-        r_in: &Ray,
+        _r_in: &Ray,
+        rec: &HitRecord,
+        _uc: f64,
+        _u: (f64, f64),
+    ) -> Option<ScatterRecord> {
+        Some(ScatterRecord::diffuse(
+            self.albedo,
+            CosinePdf::new(&rec.normal),
+        ))
+    }
+
+    fn eval(&self, _r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+        let cos_theta = Vector3::dot(&rec.normal, &Vector3::unit_vector(&scattered.direction()));
+        f64::max(0.0, cos_theta / PI) * &self.albedo
+    }
+}
+
+// Oren-Nayar diffuse (qualitative model), for rough surfaces made of many tiny Lambertian facets.
+// Sigma is the standard deviation of the facet slope angle in degrees; zero reduces to Lambertian
+pub struct OrenNayar {
+    albedo: Color,
+    a: f64,
+    b: f64,
+}
+
+impl OrenNayar {
+    pub fn new(albedo: Color, sigma: f64) -> Self {
+        let sigma = utils::degrees_to_radians(sigma);
+        let sigma2 = sigma * sigma;
+
+        Self {
+            albedo,
+            a: 1.0 - sigma2 / (2.0 * (sigma2 + 0.33)),
+            b: 0.45 * sigma2 / (sigma2 + 0.09),
+        }
+    }
+}
+
+impl Material for OrenNayar {
+    fn scatter(
+        &self,
+        _r_in: &Ray,
         rec: &HitRecord,
-        attenuation: &mut Color,
-        scattered: &mut Ray,
-    ) -> bool {
-        let mut scatter_direction = &rec.normal + &Vector3::random_unit_vector();
+        _uc: f64,
+        _u: (f64, f64),
+    ) -> Option<ScatterRecord> {
+        Some(ScatterRecord::diffuse(
+            self.albedo,
+            CosinePdf::new(&rec.normal),
+        ))
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+        let wo = -Vector3::unit_vector(&r_in.direction());
+        let wi = Vector3::unit_vector(&scattered.direction());
+        let cos_i = Vector3::dot(&rec.normal, &wi);
+        let cos_o = Vector3::dot(&rec.normal, &wo).max(0.0);
+        if cos_i <= 0.0 {
+            return Color::zero();
+        }
+
+        let sin_i = f64::sqrt((1.0 - cos_i * cos_i).max(0.0));
+        let sin_o = f64::sqrt((1.0 - cos_o * cos_o).max(0.0));
 
-        if scatter_direction.near_zero() {
-            scatter_direction = rec.normal;
+        // Cosine of the azimuthal angle between the two directions, from their tangent projections
+        let mut max_cos = 0.0;
+        if sin_i > 1e-4 && sin_o > 1e-4 {
+            let tangent_i = &wi - &(cos_i * &rec.normal);
+            let tangent_o = &wo - &(cos_o * &rec.normal);
+            max_cos = f64::max(0.0, Vector3::dot(&tangent_i, &tangent_o) / (sin_i * sin_o));
         }
 
-        *scattered = Ray::new(&rec.p, &scatter_direction);
-        *attenuation = self.albedo;
-        true
+        let (sin_alpha, tan_beta) = if cos_i > cos_o {
+            (sin_o, sin_i / cos_i)
+        } else {
+            (sin_i, sin_o / cos_o.max(1e-8))
+        };
+
+        let f = (self.a + self.b * max_cos * sin_alpha * tan_beta) / PI;
+        (f * cos_i) * &self.albedo
     }
 }
 
@@ -71,27 +181,91 @@ impl Material for Metal {
         &self,
         r_in: &Ray,
         rec: &HitRecord,
-        attenuation: &mut Color,
-        scattered: &mut Ray,
-    ) -> bool {
+        _uc: f64,
+        u: (f64, f64),
+    ) -> Option<ScatterRecord> {
         let reflected = Vector3::reflect(&r_in.direction(), &rec.normal);
         let reflected =
-            &Vector3::unit_vector(&reflected) + &(self.fuzz * &Vector3::random_unit_vector());
+            &Vector3::unit_vector(&reflected) + &(self.fuzz * &sampler::uniform_sphere(u));
+
+        if Vector3::dot(&reflected, &rec.normal) <= 0.0 {
+            return None;
+        }
+
+        Some(ScatterRecord::specular(
+            self.albedo,
+            Ray::new(&rec.p, &reflected),
+        ))
+    }
 
-        *scattered = Ray::new(&rec.p, &reflected);
-        *attenuation = self.albedo;
+    fn specular_probability(&self, _r_in: &Ray, _rec: &HitRecord) -> f64 {
+        1.0
+    }
+}
 
-        Vector3::dot(&scattered.direction(), &rec.normal) > 0.0
+// Beer-Lambert transmittance along 'r_in' when it travelled inside the object to reach 'rec'
+fn interior_transmittance(absorption: &Color, r_in: &Ray, rec: &HitRecord) -> Color {
+    if rec.front_face {
+        return Color::new(1.0, 1.0, 1.0);
     }
+
+    let distance = rec.t * r_in.direction().length();
+    Color::new(
+        f64::exp(-absorption.x * distance),
+        f64::exp(-absorption.y * distance),
+        f64::exp(-absorption.z * distance),
+    )
 }
 
 pub struct Dielectric {
     refraction_index: f64,
+    exact_fresnel: bool,
+    absorption: Color,
+    dispersion: Option<Dispersion>,
+    thin_film: Option<ThinFilm>,
 }
 
 impl Dielectric {
     pub fn new(refraction_index: f64) -> Self {
-        Self { refraction_index }
+        Self {
+            refraction_index,
+            exact_fresnel: false,
+            absorption: Color::zero(),
+            dispersion: None,
+            thin_film: None,
+        }
+    }
+
+    // Wavelength-dependent index of refraction; RGB paths use its value at the sodium D line
+    pub fn with_dispersion(mut self, dispersion: Dispersion) -> Self {
+        self.refraction_index = dispersion.refraction_index(spectrum::LAMBDA_D);
+        self.dispersion = Some(dispersion);
+        self
+    }
+
+    fn refraction_index(&self, wavelength: Option<f64>) -> f64 {
+        match (self.dispersion, wavelength) {
+            (Some(dispersion), Some(lambda)) => dispersion.refraction_index(lambda),
+            _ => self.refraction_index,
+        }
+    }
+
+    // Absorption coefficient per unit distance travelled inside, for tinted glass and liquids
+    pub fn with_absorption(mut self, absorption: Color) -> Self {
+        self.absorption = absorption;
+        self
+    }
+
+    // Use the full Fresnel equations instead of Schlick's approximation
+    pub fn with_exact_fresnel(mut self) -> Self {
+        self.exact_fresnel = true;
+        self
+    }
+
+    // Coats the surface with a film whose interference colors its reflections
+    pub fn with_thin_film(mut self, thin_film: ThinFilm) -> Self {
+        self.thin_film = Some(thin_film);
+        self
     }
 
     pub fn reflectance(cosine: f64, refraction_index: f64) -> f64 {
@@ -107,15 +281,14 @@ impl Material for Dielectric {
         &self,
         r_in: &Ray,
         rec: &HitRecord,
-        attenuation: &mut Color,
-        scattered: &mut Ray,
-    ) -> bool {
-        *attenuation = Color::new(1.0, 1.0, 1.0);
-
+        uc: f64,
+        _u: (f64, f64),
+    ) -> Option<ScatterRecord> {
+        let refraction_index = self.refraction_index(r_in.wavelength());
         let ri = if rec.front_face {
-            1.0 / self.refraction_index
+            1.0 / refraction_index
         } else {
-            self.refraction_index
+            refraction_index
         };
 
         let unit_direction = Vector3::unit_vector(&r_in.direction());
@@ -123,15 +296,536 @@ impl Material for Dielectric {
         let sin_theta = f64::sqrt(1.0 - cos_theta * cos_theta);
 
         let cannot_refract = ri * sin_theta > 1.0;
-        let direction = if cannot_refract
-            || Dielectric::reflectance(cos_theta, ri) > utils::canonical_random_number()
-        {
-            Vector3::reflect(&unit_direction, &rec.normal)
+        let reflectance = match self.thin_film {
+            Some(film) => {
+                let (n_incident, n_transmitted) = if rec.front_face {
+                    (1.0, refraction_index)
+                } else {
+                    (refraction_index, 1.0)
+                };
+                film.dielectric_reflectance(cos_theta, n_incident, n_transmitted, r_in.wavelength())
+            }
+            None => {
+                let r = if self.exact_fresnel {
+                    microfacet::fresnel_dielectric(cos_theta, 1.0 / ri)
+                } else {
+                    Dielectric::reflectance(cos_theta, ri)
+                };
+                Color::new(r, r, r)
+            }
+        };
+
+        // A film can reflect each channel differently, so reflection is chosen with the mean
+        // reflectance and the outcome is reweighted per channel
+        let probability = (reflectance.x + reflectance.y + reflectance.z) / 3.0;
+        let (direction, weight) = if cannot_refract {
+            (
+                Vector3::reflect(&unit_direction, &rec.normal),
+                Color::new(1.0, 1.0, 1.0),
+            )
+        } else if uc < probability {
+            (
+                Vector3::reflect(&unit_direction, &rec.normal),
+                &reflectance / probability,
+            )
+        } else {
+            (
+                Vector3::refract(&unit_direction, &rec.normal, ri),
+                &(&Color::new(1.0, 1.0, 1.0) - &reflectance) / (1.0 - probability),
+            )
+        };
+
+        Some(ScatterRecord::specular(
+            &weight * &interior_transmittance(&self.absorption, r_in, rec),
+            Ray::new(&rec.p, &direction),
+        ))
+    }
+
+    fn specular_probability(&self, _r_in: &Ray, _rec: &HitRecord) -> f64 {
+        1.0
+    }
+}
+
+pub struct Conductor {
+    // Complex index of refraction, eta + i k, per color channel
+    eta: Color,
+    k: Color,
+    distribution: TrowbridgeReitz,
+    thin_film: Option<ThinFilm>,
+}
+
+impl Conductor {
+    pub fn new(eta: Color, k: Color, roughness: f64) -> Self {
+        Conductor::anisotropic(eta, k, roughness, roughness)
+    }
+
+    // Roughness along the tangent and bitangent of the surface, which follow its texture
+    // coordinates
+    pub fn anisotropic(eta: Color, k: Color, roughness_u: f64, roughness_v: f64) -> Self {
+        Self {
+            eta,
+            k,
+            distribution: TrowbridgeReitz::new(roughness_u, roughness_v),
+            thin_film: None,
+        }
+    }
+
+    // Coats the metal with a film, such as an oxide layer, whose interference colors it
+    pub fn with_thin_film(mut self, thin_film: ThinFilm) -> Self {
+        self.thin_film = Some(thin_film);
+        self
+    }
+
+    fn fresnel(&self, cos_theta: f64, wavelength: Option<f64>) -> Color {
+        match self.thin_film {
+            Some(film) => film.conductor_reflectance(cos_theta, &self.eta, &self.k, wavelength),
+            None => microfacet::fresnel_conductor(cos_theta, &self.eta, &self.k),
+        }
+    }
+
+    pub fn gold(roughness: f64) -> Self {
+        Conductor::new(
+            Color::new(0.143, 0.374, 1.442),
+            Color::new(3.983, 2.385, 1.603),
+            roughness,
+        )
+    }
+
+    pub fn copper(roughness: f64) -> Self {
+        Conductor::new(
+            Color::new(0.200, 0.924, 1.102),
+            Color::new(3.912, 2.452, 2.142),
+            roughness,
+        )
+    }
+
+    pub fn aluminium(roughness: f64) -> Self {
+        Conductor::new(
+            Color::new(1.657, 0.880, 0.521),
+            Color::new(9.224, 6.270, 4.837),
+            roughness,
+        )
+    }
+
+    pub fn silver(roughness: f64) -> Self {
+        Conductor::new(
+            Color::new(0.155, 0.117, 0.138),
+            Color::new(4.828, 3.122, 2.147),
+            roughness,
+        )
+    }
+}
+
+impl Material for Conductor {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        _uc: f64,
+        _u: (f64, f64),
+    ) -> Option<ScatterRecord> {
+        let unit_direction = Vector3::unit_vector(&r_in.direction());
+
+        if self.distribution.is_smooth() {
+            let cos_theta = f64::min(Vector3::dot(&-unit_direction, &rec.normal), 1.0);
+            let reflected = Vector3::reflect(&unit_direction, &rec.normal);
+
+            return Some(ScatterRecord::specular(
+                self.fresnel(cos_theta, r_in.wavelength()),
+                Ray::new(&rec.p, &reflected),
+            ));
+        }
+
+        Some(ScatterRecord::diffuse(
+            Color::new(1.0, 1.0, 1.0),
+            MicrofacetReflectionPdf::new(
+                Onb::with_tangent(&rec.normal, &rec.tangent),
+                &-unit_direction,
+                self.distribution,
+            ),
+        ))
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+        let uvw = Onb::with_tangent(&rec.normal, &rec.tangent);
+        let wo = uvw.to_basis(&-Vector3::unit_vector(&r_in.direction()));
+        let wi = uvw.to_basis(&Vector3::unit_vector(&scattered.direction()));
+        if wo.z <= 0.0 || wi.z <= 0.0 {
+            return Color::zero();
+        }
+
+        let wm = Vector3::unit_vector(&(&wo + &wi));
+        let fresnel = self.fresnel(Vector3::dot(&wo, &wm).abs(), r_in.wavelength());
+
+        // D G F / (4 cos_o cos_i), times cos_i
+        let d = self.distribution.d(&wm);
+        let g = self.distribution.g(&wo, &wi);
+        (d * g / (4.0 * wo.z)) * &fresnel
+    }
+
+    fn specular_probability(&self, _r_in: &Ray, _rec: &HitRecord) -> f64 {
+        if self.distribution.is_smooth() {
+            1.0
+        } else {
+            0.0
+        }
+    }
+}
+
+pub struct RoughDielectric {
+    refraction_index: f64,
+    distribution: TrowbridgeReitz,
+    absorption: Color,
+}
+
+impl RoughDielectric {
+    pub fn new(refraction_index: f64, roughness: f64) -> Self {
+        Self {
+            refraction_index,
+            distribution: TrowbridgeReitz::new(roughness, roughness),
+            absorption: Color::zero(),
+        }
+    }
+
+    pub fn with_absorption(mut self, absorption: Color) -> Self {
+        self.absorption = absorption;
+        self
+    }
+
+    fn eta(&self, rec: &HitRecord) -> f64 {
+        // Index of refraction across the surface over the index on the side of the normal
+        if rec.front_face {
+            self.refraction_index
+        } else {
+            1.0 / self.refraction_index
+        }
+    }
+}
+
+impl Material for RoughDielectric {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        uc: f64,
+        u: (f64, f64),
+    ) -> Option<ScatterRecord> {
+        if self.distribution.is_smooth() {
+            return Dielectric::new(self.refraction_index)
+                .with_exact_fresnel()
+                .with_absorption(self.absorption)
+                .scatter(r_in, rec, uc, u);
+        }
+
+        Some(ScatterRecord::diffuse(
+            Color::new(1.0, 1.0, 1.0),
+            MicrofacetDielectricPdf::new(
+                Onb::new(&rec.normal),
+                &-r_in.direction(),
+                self.eta(rec),
+                self.distribution,
+            ),
+        ))
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+        let uvw = Onb::new(&rec.normal);
+        let wo = uvw.to_basis(&-Vector3::unit_vector(&r_in.direction()));
+        let wi = uvw.to_basis(&Vector3::unit_vector(&scattered.direction()));
+        let f_cos = self.distribution.eval_dielectric(&wo, &wi, self.eta(rec));
+
+        f_cos * &interior_transmittance(&self.absorption, r_in, rec)
+    }
+
+    fn specular_probability(&self, _r_in: &Ray, _rec: &HitRecord) -> f64 {
+        if self.distribution.is_smooth() {
+            1.0
+        } else {
+            0.0
+        }
+    }
+}
+
+// Blends two materials, weighting 'second' by the mask's luminance. Specular scatters are
+// picked with their material's weight, while the other lobes are sampled together so that their
+// blend in 'eval' is importance sampled
+pub struct MixMaterial {
+    first: Rc<dyn Material>,
+    second: Rc<dyn Material>,
+    mask: Rc<dyn Texture>,
+}
+
+impl MixMaterial {
+    pub fn new(
+        first: Rc<dyn Material>,
+        second: Rc<dyn Material>,
+        mask: impl Texture + 'static,
+    ) -> Self {
+        Self {
+            first,
+            second,
+            mask: Rc::new(mask),
+        }
+    }
+
+    fn amount(&self, rec: &HitRecord) -> f64 {
+        self.mask
+            .value(rec.u, rec.v, &rec.p)
+            .luminance()
+            .clamp(0.0, 1.0)
+    }
+}
+
+impl Material for MixMaterial {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        uc: f64,
+        u: (f64, f64),
+    ) -> Option<ScatterRecord> {
+        let amount = self.amount(rec);
+
+        // 'uc' picks a material, then is stretched back over [0, 1) for it to use
+        let (chosen, weight, other, other_weight, uc) = if uc < amount {
+            (&self.second, amount, &self.first, 1.0 - amount, uc / amount)
         } else {
-            Vector3::refract(&unit_direction, &rec.normal, ri)
+            let uc = (uc - amount) / (1.0 - amount);
+            (&self.first, 1.0 - amount, &self.second, amount, uc)
         };
+        let uc = f64::min(uc, 1.0 - f64::EPSILON);
+
+        let mut srec = chosen.scatter(r_in, rec, uc, u)?;
+        if srec.is_specular {
+            return Some(srec);
+        }
 
-        *scattered = Ray::new(&rec.p, &direction);
-        true
+        // Whichever material was picked, the directions that are not specular come from both
+        // materials' lobes in proportion to their share of those scatters, as in 'eval'. The other
+        // material is probed at the top of 'uc', where layered materials scatter off their base
+        let mut pdf = MixturePdf::new();
+        pdf.add(
+            weight * (1.0 - chosen.specular_probability(r_in, rec)),
+            srec.pdf.take()?,
+        );
+        if other_weight > 0.0 {
+            let other_pdf = other
+                .scatter(r_in, rec, 1.0 - f64::EPSILON, u)
+                .filter(|other| !other.is_specular)
+                .and_then(|other| other.pdf);
+            if let Some(other_pdf) = other_pdf {
+                pdf.add(
+                    other_weight * (1.0 - other.specular_probability(r_in, rec)),
+                    other_pdf,
+                );
+            }
+        }
+
+        srec.pdf = Some(Box::new(pdf));
+        Some(srec)
+    }
+
+    // The blend of the two materials over the scatters that are not specular, so a specular
+    // partner leaves the other material's BSDF unweighted
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+        let amount = self.amount(rec);
+        let first = (1.0 - amount) * (1.0 - self.first.specular_probability(r_in, rec));
+        let second = amount * (1.0 - self.second.specular_probability(r_in, rec));
+        if first + second <= 0.0 {
+            return Color::zero();
+        }
+
+        let mut f = Color::zero();
+        if first > 0.0 {
+            f = &f + &(first * &self.first.eval(r_in, rec, scattered));
+        }
+        if second > 0.0 {
+            f = &f + &(second * &self.second.eval(r_in, rec, scattered));
+        }
+        &f / (first + second)
+    }
+
+    fn specular_probability(&self, r_in: &Ray, rec: &HitRecord) -> f64 {
+        let amount = self.amount(rec);
+        (1.0 - amount) * self.first.specular_probability(r_in, rec)
+            + amount * self.second.specular_probability(r_in, rec)
+    }
+
+    fn alpha(&self, rec: &HitRecord) -> f64 {
+        let amount = self.amount(rec);
+        (1.0 - amount) * self.first.alpha(rec) + amount * self.second.alpha(rec)
+    }
+}
+
+// A smooth dielectric coating over another material, like varnish or the clear coat of car paint.
+// Light either reflects off the coating, or crosses it, scatters off the base and crosses it again,
+// losing the Fresnel reflectance and the coating's absorption each way. Light reflected back down
+// by the underside of the coating is not returned, so the layer never gains energy
+pub struct LayeredMaterial {
+    base: Rc<dyn Material>,
+    refraction_index: f64,
+    // Transmittance of the coating for a single pass at normal incidence
+    tint: Color,
+}
+
+impl LayeredMaterial {
+    pub fn new(base: Rc<dyn Material>, refraction_index: f64) -> Self {
+        Self {
+            base,
+            refraction_index,
+            tint: Color::new(1.0, 1.0, 1.0),
+        }
+    }
+
+    pub fn with_tint(mut self, tint: Color) -> Self {
+        self.tint = tint;
+        self
+    }
+
+    // Absorption of the coating when crossing it at 'cos_theta'; the path through it grows
+    // with the refracted angle
+    fn absorption(&self, cos_theta: f64) -> Color {
+        let cos_theta = cos_theta.clamp(0.0, 1.0);
+        let sin2_t = (1.0 - cos_theta * cos_theta) / self.refraction_index.powi(2);
+        let path = 1.0 / f64::sqrt(1.0 - sin2_t).max(1e-4);
+
+        Color::new(
+            self.tint.x.powf(path),
+            self.tint.y.powf(path),
+            self.tint.z.powf(path),
+        )
+    }
+
+    fn fresnel(&self, cos_theta: f64) -> f64 {
+        microfacet::fresnel_dielectric(cos_theta.clamp(0.0, 1.0), self.refraction_index)
+    }
+
+    // Weight of a path through the base: the coating's absorption on the way in, since crossing
+    // it was chosen with probability 1 - F, then Fresnel transmittance and absorption out
+    fn coating(&self, cos_o: f64, cos_i: f64) -> Color {
+        let absorption = &self.absorption(cos_o) * &self.absorption(cos_i);
+        (1.0 - self.fresnel(cos_i)) * &absorption
+    }
+}
+
+impl Material for LayeredMaterial {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        uc: f64,
+        u: (f64, f64),
+    ) -> Option<ScatterRecord> {
+        // The coating is only on the outside
+        if !rec.front_face {
+            return self.base.scatter(r_in, rec, uc, u);
+        }
+
+        let unit_direction = Vector3::unit_vector(&r_in.direction());
+        let cos_o = Vector3::dot(&-unit_direction, &rec.normal);
+
+        // Reflecting off the coating is chosen with its own probability, so it has unit weight
+        let fresnel = self.fresnel(cos_o);
+        if uc < fresnel {
+            let reflected = Vector3::reflect(&unit_direction, &rec.normal);
+            return Some(ScatterRecord::specular(
+                Color::new(1.0, 1.0, 1.0),
+                Ray::new(&rec.p, &reflected),
+            ));
+        }
+
+        // The rest of 'uc' is stretched back over [0, 1) for the base
+        let uc = f64::min((uc - fresnel) / (1.0 - fresnel), 1.0 - f64::EPSILON);
+        let mut srec = self.base.scatter(r_in, rec, uc, u)?;
+        if srec.is_specular {
+            let cos_i = Vector3::dot(
+                &Vector3::unit_vector(&srec.specular_ray.direction()),
+                &rec.normal,
+            );
+            srec.attenuation = &srec.attenuation * &self.coating(cos_o, cos_i);
+        }
+
+        Some(srec)
+    }
+
+    // Only reached through the base, after the coating was crossed on the way in
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+        let base = self.base.eval(r_in, rec, scattered);
+        if !rec.front_face {
+            return base;
+        }
+
+        let cos_o = Vector3::dot(&-Vector3::unit_vector(&r_in.direction()), &rec.normal);
+        let cos_i = Vector3::dot(&Vector3::unit_vector(&scattered.direction()), &rec.normal);
+
+        &base * &self.coating(cos_o, cos_i)
+    }
+
+    fn specular_probability(&self, r_in: &Ray, rec: &HitRecord) -> f64 {
+        let base = self.base.specular_probability(r_in, rec);
+        if !rec.front_face {
+            return base;
+        }
+
+        let cos_o = Vector3::dot(&-Vector3::unit_vector(&r_in.direction()), &rec.normal);
+        let fresnel = self.fresnel(cos_o);
+        fresnel + (1.0 - fresnel) * base
+    }
+
+    fn alpha(&self, rec: &HitRecord) -> f64 {
+        self.base.alpha(rec)
+    }
+}
+
+// Cuts 'base' out where the alpha texture's luminance is below 'threshold', for leaves, fences
+// and decals. Alpha at or above the threshold is treated as coverage, so fractional values
+// render as stochastic transparency
+pub struct AlphaMask {
+    base: Rc<dyn Material>,
+    alpha: Rc<dyn Texture>,
+    threshold: f64,
+}
+
+impl AlphaMask {
+    pub fn new(base: Rc<dyn Material>, alpha: impl Texture + 'static) -> Self {
+        Self {
+            base,
+            alpha: Rc::new(alpha),
+            threshold: 0.0,
+        }
+    }
+
+    pub fn with_threshold(mut self, threshold: f64) -> Self {
+        self.threshold = threshold;
+        self
+    }
+}
+
+impl Material for AlphaMask {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        uc: f64,
+        u: (f64, f64),
+    ) -> Option<ScatterRecord> {
+        self.base.scatter(r_in, rec, uc, u)
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+        self.base.eval(r_in, rec, scattered)
+    }
+
+    fn specular_probability(&self, r_in: &Ray, rec: &HitRecord) -> f64 {
+        self.base.specular_probability(r_in, rec)
+    }
+
+    fn alpha(&self, rec: &HitRecord) -> f64 {
+        let alpha = self.alpha.value(rec.u, rec.v, &rec.p).luminance();
+        if alpha < self.threshold {
+            0.0
+        } else {
+            alpha.clamp(0.0, 1.0) * self.base.alpha(rec)
+        }
     }
 }
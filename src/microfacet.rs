@@ -0,0 +1,419 @@
+use std::f64::consts::PI;
+
+use crate::{color::Color, onb::Onb, pdf::Pdf, vector3::Vector3};
+
+// Directions below are in basis coordinates of the shading frame, where +z is the normal
+
+#[derive(Clone, Copy)]
+pub struct TrowbridgeReitz {
+    alpha_x: f64,
+    alpha_y: f64,
+}
+
+impl TrowbridgeReitz {
+    // Roughness is perceptually linear in [0, 1]; alpha is its square
+    pub fn new(roughness_x: f64, roughness_y: f64) -> Self {
+        Self {
+            alpha_x: TrowbridgeReitz::roughness_to_alpha(roughness_x),
+            alpha_y: TrowbridgeReitz::roughness_to_alpha(roughness_y),
+        }
+    }
+
+    fn roughness_to_alpha(roughness: f64) -> f64 {
+        let roughness = roughness.clamp(0.0, 1.0);
+        roughness * roughness
+    }
+
+    // Below this the surface is treated as a perfect mirror
+    pub fn is_smooth(&self) -> bool {
+        self.alpha_x.max(self.alpha_y) < 1e-3
+    }
+
+    pub fn d(&self, wm: &Vector3) -> f64 {
+        let cos2_theta = wm.z * wm.z;
+        if cos2_theta <= 0.0 {
+            return 0.0;
+        }
+
+        let e = (wm.x * wm.x / (self.alpha_x * self.alpha_x)
+            + wm.y * wm.y / (self.alpha_y * self.alpha_y))
+            / cos2_theta;
+        1.0 / (PI * self.alpha_x * self.alpha_y * cos2_theta * cos2_theta * (1.0 + e) * (1.0 + e))
+    }
+
+    fn lambda(&self, w: &Vector3) -> f64 {
+        if w.z == 0.0 {
+            return f64::INFINITY;
+        }
+
+        let alpha2_tan2_theta = (w.x * w.x * self.alpha_x * self.alpha_x
+            + w.y * w.y * self.alpha_y * self.alpha_y)
+            / (w.z * w.z);
+        (f64::sqrt(1.0 + alpha2_tan2_theta) - 1.0) / 2.0
+    }
+
+    pub fn g1(&self, w: &Vector3) -> f64 {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    // Height-correlated Smith masking-shadowing
+    pub fn g(&self, wo: &Vector3, wi: &Vector3) -> f64 {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    // Distribution of normals visible from 'w'
+    pub fn d_visible(&self, w: &Vector3, wm: &Vector3) -> f64 {
+        if w.z == 0.0 {
+            return 0.0;
+        }
+        self.g1(w) / f64::abs(w.z) * self.d(wm) * f64::abs(Vector3::dot(w, wm))
+    }
+
+    // Rough dielectric BSDF times |cos_i|, covering both reflection and transmission. 'eta' is
+    // the index of refraction below the surface over the one above it
+    pub fn eval_dielectric(&self, wo: &Vector3, wi: &Vector3, eta: f64) -> f64 {
+        let Some(wm) = half_vector(wo, wi, eta) else {
+            return 0.0;
+        };
+
+        let reflectance = fresnel_dielectric(Vector3::dot(wo, &wm), eta);
+        let d = self.d(&wm);
+        let g = self.g(wo, wi);
+
+        // Both lobes are multiplied by |cos_i|, which cancels in the reflection term
+        if wo.z * wi.z > 0.0 {
+            d * g * reflectance / (4.0 * wo.z.abs())
+        } else {
+            // Radiance is compressed by 1/eta^2 when entering a denser medium
+            let denom = Vector3::dot(wi, &wm) + Vector3::dot(wo, &wm) / eta;
+            d * (1.0 - reflectance)
+                * g
+                * f64::abs(Vector3::dot(wi, &wm) * Vector3::dot(wo, &wm) / (denom * denom * wo.z))
+                / (eta * eta)
+        }
+    }
+
+    // Samples a microfacet normal from the visible normal distribution (Heitz 2018)
+    pub fn sample_wm(&self, w: &Vector3, u: (f64, f64)) -> Vector3 {
+        // Transform the view direction to the hemisphere configuration
+        let mut wh =
+            Vector3::unit_vector(&Vector3::new(self.alpha_x * w.x, self.alpha_y * w.y, w.z));
+        if wh.z < 0.0 {
+            wh = -wh;
+        }
+
+        let length_squared = wh.x * wh.x + wh.y * wh.y;
+        let t1 = if length_squared > 0.0 {
+            &Vector3::new(-wh.y, wh.x, 0.0) / length_squared.sqrt()
+        } else {
+            Vector3::new(1.0, 0.0, 0.0)
+        };
+        let t2 = Vector3::cross(&wh, &t1);
+
+        // Sample a point on the projected half disk
+        let r = u.0.sqrt();
+        let phi = 2.0 * PI * u.1;
+        let p1 = r * f64::cos(phi);
+        let p2 = r * f64::sin(phi);
+        let s = 0.5 * (1.0 + wh.z);
+        let p2 = (1.0 - s) * f64::sqrt(1.0 - p1 * p1) + s * p2;
+
+        // Reproject onto the hemisphere and transform back to the ellipsoid configuration
+        let p3 = f64::sqrt(f64::max(0.0, 1.0 - p1 * p1 - p2 * p2));
+        let nh = &(&(p1 * &t1) + &(p2 * &t2)) + &(p3 * &wh);
+        Vector3::unit_vector(&Vector3::new(
+            self.alpha_x * nh.x,
+            self.alpha_y * nh.y,
+            f64::max(1e-6, nh.z),
+        ))
+    }
+}
+
+// Fresnel reflectance of a dielectric interface, where 'eta' is the index of refraction on the
+// transmitted side over the index on the incident side
+pub fn fresnel_dielectric(cos_theta_i: f64, eta: f64) -> f64 {
+    let mut cos_theta_i = cos_theta_i.clamp(-1.0, 1.0);
+    let mut eta = eta;
+    if cos_theta_i < 0.0 {
+        eta = 1.0 / eta;
+        cos_theta_i = -cos_theta_i;
+    }
+
+    let sin2_theta_t = (1.0 - cos_theta_i * cos_theta_i) / (eta * eta);
+    if sin2_theta_t >= 1.0 {
+        // Total internal reflection
+        return 1.0;
+    }
+    let cos_theta_t = f64::sqrt(1.0 - sin2_theta_t);
+
+    let r_parallel = (eta * cos_theta_i - cos_theta_t) / (eta * cos_theta_i + cos_theta_t);
+    let r_perpendicular = (cos_theta_i - eta * cos_theta_t) / (cos_theta_i + eta * cos_theta_t);
+    (r_parallel * r_parallel + r_perpendicular * r_perpendicular) / 2.0
+}
+
+// Refracts 'wi' through the interface with normal 'n'; None on total internal reflection
+pub fn refract(wi: &Vector3, n: &Vector3, eta: f64) -> Option<Vector3> {
+    let mut cos_theta_i = Vector3::dot(n, wi);
+    let mut eta = eta;
+    let mut n = *n;
+    if cos_theta_i < 0.0 {
+        eta = 1.0 / eta;
+        cos_theta_i = -cos_theta_i;
+        n = -n;
+    }
+
+    let sin2_theta_t = (1.0 - cos_theta_i * cos_theta_i) / (eta * eta);
+    if sin2_theta_t >= 1.0 {
+        return None;
+    }
+    let cos_theta_t = f64::sqrt(1.0 - sin2_theta_t);
+
+    Some(&(&-*wi / eta) + &((cos_theta_i / eta - cos_theta_t) * &n))
+}
+
+// Fresnel reflectance of a conductor with complex index of refraction eta + i k
+pub fn fresnel_conductor(cos_theta_i: f64, eta: &Color, k: &Color) -> Color {
+    let channel = |eta: f64, k: f64| {
+        let cos2 = cos_theta_i.clamp(0.0, 1.0).powi(2);
+        let sin2 = 1.0 - cos2;
+        let eta2 = eta * eta;
+        let k2 = k * k;
+
+        let t0 = eta2 - k2 - sin2;
+        let a2_plus_b2 = f64::sqrt(t0 * t0 + 4.0 * eta2 * k2);
+        let t1 = a2_plus_b2 + cos2;
+        let a = f64::sqrt(f64::max(0.0, 0.5 * (a2_plus_b2 + t0)));
+        let t2 = 2.0 * cos_theta_i * a;
+        let rs = (t1 - t2) / (t1 + t2);
+
+        let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+        let t4 = t2 * sin2;
+        let rp = rs * (t3 - t4) / (t3 + t4);
+
+        0.5 * (rp + rs)
+    };
+
+    Color::new(
+        channel(eta.x, k.x),
+        channel(eta.y, k.y),
+        channel(eta.z, k.z),
+    )
+}
+
+pub struct MicrofacetReflectionPdf {
+    uvw: Onb,
+    wo: Vector3,
+    distribution: TrowbridgeReitz,
+}
+
+impl MicrofacetReflectionPdf {
+    // 'wo' points away from the surface, in world space
+    pub fn new(uvw: Onb, wo: &Vector3, distribution: TrowbridgeReitz) -> Self {
+        Self {
+            wo: uvw.to_basis(&Vector3::unit_vector(wo)),
+            uvw,
+            distribution,
+        }
+    }
+}
+
+impl Pdf for MicrofacetReflectionPdf {
+    fn value(&self, direction: &Vector3) -> f64 {
+        let wi = self.uvw.to_basis(&Vector3::unit_vector(direction));
+        if wi.z <= 0.0 || self.wo.z <= 0.0 {
+            return 0.0;
+        }
+
+        let wm = Vector3::unit_vector(&(&self.wo + &wi));
+        self.distribution.d_visible(&self.wo, &wm) / (4.0 * Vector3::dot(&self.wo, &wm).abs())
+    }
+
+    fn generate(&self, _uc: f64, u: (f64, f64)) -> Vector3 {
+        let wm = self.distribution.sample_wm(&self.wo, u);
+        let wi = Vector3::reflect(&-self.wo, &wm);
+        self.uvw.transform(&wi)
+    }
+}
+
+// Generalized half vector of a reflection or refraction between 'wo' and 'wi', facing +z.
+// 'eta' is the index of refraction below the surface over the one above it
+pub fn half_vector(wo: &Vector3, wi: &Vector3, eta: f64) -> Option<Vector3> {
+    if wo.z == 0.0 || wi.z == 0.0 {
+        return None;
+    }
+
+    let reflect = wo.z * wi.z > 0.0;
+    let etap = if reflect { 1.0 } else { eta };
+    let wm = &(etap * wi) + wo;
+    if wm.near_zero() {
+        return None;
+    }
+
+    let mut wm = Vector3::unit_vector(&wm);
+    if wm.z < 0.0 {
+        wm = -wm;
+    }
+
+    // Discard back-facing microfacets
+    if Vector3::dot(&wm, wi) * wi.z < 0.0 || Vector3::dot(&wm, wo) * wo.z < 0.0 {
+        return None;
+    }
+    Some(wm)
+}
+
+pub struct MicrofacetDielectricPdf {
+    uvw: Onb,
+    wo: Vector3,
+    eta: f64,
+    distribution: TrowbridgeReitz,
+}
+
+impl MicrofacetDielectricPdf {
+    // 'wo' points away from the surface on the side of the normal, in world space
+    pub fn new(uvw: Onb, wo: &Vector3, eta: f64, distribution: TrowbridgeReitz) -> Self {
+        Self {
+            wo: uvw.to_basis(&Vector3::unit_vector(wo)),
+            uvw,
+            eta,
+            distribution,
+        }
+    }
+}
+
+impl Pdf for MicrofacetDielectricPdf {
+    fn value(&self, direction: &Vector3) -> f64 {
+        if direction.near_zero() {
+            return 0.0;
+        }
+
+        let wi = self.uvw.to_basis(&Vector3::unit_vector(direction));
+        let Some(wm) = half_vector(&self.wo, &wi, self.eta) else {
+            return 0.0;
+        };
+
+        // Reflection and transmission are chosen in proportion to the Fresnel terms
+        let reflectance = fresnel_dielectric(Vector3::dot(&self.wo, &wm), self.eta);
+        let visible = self.distribution.d_visible(&self.wo, &wm);
+
+        if self.wo.z * wi.z > 0.0 {
+            visible / (4.0 * Vector3::dot(&self.wo, &wm).abs()) * reflectance
+        } else {
+            let denom = Vector3::dot(&wi, &wm) + Vector3::dot(&self.wo, &wm) / self.eta;
+            let dwm_dwi = Vector3::dot(&wi, &wm).abs() / (denom * denom);
+            visible * dwm_dwi * (1.0 - reflectance)
+        }
+    }
+
+    fn generate(&self, uc: f64, u: (f64, f64)) -> Vector3 {
+        let wm = self.distribution.sample_wm(&self.wo, u);
+        let reflectance = fresnel_dielectric(Vector3::dot(&self.wo, &wm), self.eta);
+
+        let wi = if uc < reflectance {
+            Vector3::reflect(&-self.wo, &wm)
+        } else {
+            match refract(&self.wo, &wm, self.eta) {
+                Some(wi) => wi,
+                None => return Vector3::zero(),
+            }
+        };
+
+        self.uvw.transform(&wi)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conductor_reflectance_at_normal_incidence() {
+        let eta = Color::new(0.2, 1.1, 2.5);
+        let k = Color::new(3.9, 2.4, 0.0);
+        let reflectance = fresnel_conductor(1.0, &eta, &k);
+        let expected =
+            |eta: f64, k: f64| ((eta - 1.0).powi(2) + k * k) / ((eta + 1.0).powi(2) + k * k);
+        assert!((reflectance.x - expected(eta.x, k.x)).abs() < 1e-9);
+        assert!((reflectance.y - expected(eta.y, k.y)).abs() < 1e-9);
+        assert!((reflectance.z - expected(eta.z, k.z)).abs() < 1e-9);
+
+        // Every conductor turns into a mirror at grazing angles
+        let grazing = fresnel_conductor(0.0, &eta, &k);
+        assert!((grazing.x - 1.0).abs() < 1e-9 && (grazing.y - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn dielectric_reflectance_at_normal_incidence() {
+        assert!((fresnel_dielectric(1.0, 1.5) - 0.04).abs() < 1e-12);
+        // The same from inside the denser medium, whichever way the flip is requested
+        assert!((fresnel_dielectric(1.0, 1.0 / 1.5) - 0.04).abs() < 1e-12);
+        assert!((fresnel_dielectric(-1.0, 1.5) - 0.04).abs() < 1e-12);
+        assert!((fresnel_dielectric(0.0, 1.5) - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn dielectric_total_internal_reflection() {
+        // The critical angle leaving glass is asin(1 / 1.5), about 41.8 degrees
+        let critical_cos = f64::sqrt(1.0 - 1.0 / (1.5 * 1.5));
+        assert_eq!(fresnel_dielectric(critical_cos - 1e-6, 1.0 / 1.5), 1.0);
+        assert_eq!(fresnel_dielectric(-(critical_cos - 1e-6), 1.5), 1.0);
+        assert!(fresnel_dielectric(critical_cos + 1e-3, 1.0 / 1.5) < 1.0);
+
+        let n = Vector3::new(0.0, 0.0, 1.0);
+        let wi = Vector3::new(f64::sqrt(1.0 - 0.5 * 0.5), 0.0, 0.5);
+        assert!(refract(&wi, &n, 1.0 / 1.5).is_none());
+        assert!(refract(&wi, &n, 1.5).is_some());
+    }
+
+    #[test]
+    fn dielectric_reflectance_is_reciprocal() {
+        // Light entering and leaving along the same refracted path sees the same reflectance
+        let n = Vector3::new(0.0, 0.0, 1.0);
+        for cos_theta in [0.1, 0.4, 0.7, 0.95] {
+            let wi = Vector3::new(f64::sqrt(1.0 - cos_theta * cos_theta), 0.0, cos_theta);
+            let wt = refract(&wi, &n, 1.5).unwrap();
+            let entering = fresnel_dielectric(cos_theta, 1.5);
+            let leaving = fresnel_dielectric(wt.z, 1.5);
+            assert!(
+                (entering - leaving).abs() < 1e-9,
+                "{cos_theta}: {entering} {leaving}"
+            );
+        }
+    }
+
+    // Integrates D G / (4 cos_o) over the hemisphere, the conductor's reflectance with a white
+    // Fresnel term, drawing visible normals on a stratified grid
+    fn directional_albedo(distribution: &TrowbridgeReitz, wo: &Vector3) -> f64 {
+        const N: usize = 128;
+        let mut sum = 0.0;
+        for a in 0..N {
+            for b in 0..N {
+                let u = ((a as f64 + 0.5) / N as f64, (b as f64 + 0.5) / N as f64);
+                let wm = distribution.sample_wm(wo, u);
+                let wi = Vector3::reflect(&-*wo, &wm);
+                if wi.z <= 0.0 {
+                    continue;
+                }
+                let f = distribution.d(&wm) * distribution.g(wo, &wi) / (4.0 * wo.z);
+                let pdf = distribution.d_visible(wo, &wm) / (4.0 * Vector3::dot(wo, &wm).abs());
+                sum += f / pdf;
+            }
+        }
+        sum / (N * N) as f64
+    }
+
+    #[test]
+    fn white_furnace_conductor_does_not_gain_energy() {
+        for roughness in [0.1, 0.3, 0.6, 1.0] {
+            let distribution = TrowbridgeReitz::new(roughness, roughness);
+            for cos_theta in [0.2, 0.5, 0.9] {
+                let wo = Vector3::new(f64::sqrt(1.0 - cos_theta * cos_theta), 0.0, cos_theta);
+                let albedo = directional_albedo(&distribution, &wo);
+                assert!(albedo <= 1.0 + 1e-3, "{roughness} {cos_theta}: {albedo}");
+                // Single scattering loses the energy masked between microfacets, which is
+                // negligible when smooth and up to two thirds at a roughness of 1
+                let floor = 1.0 - 0.75 * roughness;
+                assert!(albedo >= floor, "{roughness} {cos_theta}: {albedo}");
+            }
+        }
+    }
+}
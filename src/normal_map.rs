@@ -0,0 +1,169 @@
+use std::rc::Rc;
+
+use crate::{
+    color::Color,
+    hittable::HitRecord,
+    material::{Material, ScatterRecord},
+    onb::Onb,
+    ray::Ray,
+    texture::Texture,
+    vector3::Vector3,
+};
+
+// Step in texture coordinates for the finite differences of bump maps
+const BUMP_DELTA: f64 = 0.0005;
+
+// Returns a copy of 'rec' shaded with 'outward_normal', keeping its original orientation
+fn with_shading_normal(rec: &HitRecord, outward_normal: &Vector3) -> HitRecord {
+    let mut shading = rec.clone();
+    shading.normal = if rec.front_face {
+        *outward_normal
+    } else {
+        -*outward_normal
+    };
+
+    shading
+}
+
+fn outward_normal(rec: &HitRecord) -> Vector3 {
+    if rec.front_face {
+        rec.normal
+    } else {
+        -rec.normal
+    }
+}
+
+// Perturbs the shading normal of 'base' with a tangent space normal map, whose red, green and
+// blue channels hold the normal along the tangent, bitangent and surface normal, mapped to [0, 1]
+pub struct NormalMap {
+    base: Rc<dyn Material>,
+    map: Rc<dyn Texture>,
+}
+
+impl NormalMap {
+    pub fn new(base: Rc<dyn Material>, map: impl Texture + 'static) -> Self {
+        Self {
+            base,
+            map: Rc::new(map),
+        }
+    }
+
+    fn shade(&self, rec: &HitRecord) -> HitRecord {
+        let normal = outward_normal(rec);
+        let frame = Onb::with_tangent(&normal, &rec.tangent);
+
+        // Mirrored texture coordinates flip the bitangent relative to the frame
+        let handedness =
+            if Vector3::dot(&rec.bitangent, &Vector3::cross(&normal, &rec.tangent)) < 0.0 {
+                -1.0
+            } else {
+                1.0
+            };
+
+        let c = self.map.value(rec.u, rec.v, &rec.p);
+        let local = Vector3::new(
+            2.0 * c.x - 1.0,
+            handedness * (2.0 * c.y - 1.0),
+            2.0 * c.z - 1.0,
+        );
+        if local.near_zero() {
+            return rec.clone();
+        }
+
+        with_shading_normal(rec, &Vector3::unit_vector(&frame.transform(&local)))
+    }
+}
+
+impl Material for NormalMap {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        uc: f64,
+        u: (f64, f64),
+    ) -> Option<ScatterRecord> {
+        self.base.scatter(r_in, &self.shade(rec), uc, u)
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+        self.base.eval(r_in, &self.shade(rec), scattered)
+    }
+
+    fn specular_probability(&self, r_in: &Ray, rec: &HitRecord) -> f64 {
+        self.base.specular_probability(r_in, &self.shade(rec))
+    }
+
+    fn alpha(&self, rec: &HitRecord) -> f64 {
+        self.base.alpha(rec)
+    }
+}
+
+// Perturbs the shading normal of 'base' as if the surface were displaced along its normal by
+// 'scale' times the height texture's luminance
+pub struct BumpMap {
+    base: Rc<dyn Material>,
+    height: Rc<dyn Texture>,
+    scale: f64,
+}
+
+impl BumpMap {
+    pub fn new(base: Rc<dyn Material>, height: impl Texture + 'static, scale: f64) -> Self {
+        Self {
+            base,
+            height: Rc::new(height),
+            scale,
+        }
+    }
+
+    fn shade(&self, rec: &HitRecord) -> HitRecord {
+        let normal = outward_normal(rec);
+        let height = |u: f64, v: f64, offset: &Vector3| {
+            let p = &rec.p + offset;
+            self.scale * self.height.value(u, v, &p).luminance()
+        };
+
+        // Forward differences of the displacement along u and v
+        let h = height(rec.u, rec.v, &Vector3::zero());
+        let du = height(rec.u + BUMP_DELTA, rec.v, &(BUMP_DELTA * &rec.tangent)) - h;
+        let dv = height(rec.u, rec.v + BUMP_DELTA, &(BUMP_DELTA * &rec.bitangent)) - h;
+
+        let dpdu = &rec.tangent + &((du / BUMP_DELTA) * &normal);
+        let dpdv = &rec.bitangent + &((dv / BUMP_DELTA) * &normal);
+        let bumped = Vector3::cross(&dpdu, &dpdv);
+        if bumped.near_zero() {
+            return rec.clone();
+        }
+
+        // Keep the bumped normal on the same side as the geometric one
+        let bumped = Vector3::unit_vector(&bumped);
+        if Vector3::dot(&bumped, &normal) < 0.0 {
+            with_shading_normal(rec, &-bumped)
+        } else {
+            with_shading_normal(rec, &bumped)
+        }
+    }
+}
+
+impl Material for BumpMap {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        uc: f64,
+        u: (f64, f64),
+    ) -> Option<ScatterRecord> {
+        self.base.scatter(r_in, &self.shade(rec), uc, u)
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+        self.base.eval(r_in, &self.shade(rec), scattered)
+    }
+
+    fn specular_probability(&self, r_in: &Ray, rec: &HitRecord) -> f64 {
+        self.base.specular_probability(r_in, &self.shade(rec))
+    }
+
+    fn alpha(&self, rec: &HitRecord) -> f64 {
+        self.base.alpha(rec)
+    }
+}
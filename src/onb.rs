@@ -0,0 +1,54 @@
+use crate::vector3::Vector3;
+
+pub struct Onb {
+    axis: [Vector3; 3],
+}
+
+impl Onb {
+    pub fn new(n: &Vector3) -> Self {
+        // Build an orthonormal basis whose w axis points along 'n'
+        let w = Vector3::unit_vector(n);
+        let a = if f64::abs(w.x) > 0.9 {
+            Vector3::new(0.0, 1.0, 0.0)
+        } else {
+            Vector3::new(1.0, 0.0, 0.0)
+        };
+        let v = Vector3::unit_vector(&Vector3::cross(&w, &a));
+        let u = Vector3::cross(&w, &v);
+
+        Self { axis: [u, v, w] }
+    }
+
+    pub fn with_tangent(n: &Vector3, tangent: &Vector3) -> Self {
+        // Build an orthonormal basis whose w axis points along 'n' and whose u axis follows the
+        // part of 'tangent' that lies in the plane normal to 'n'
+        let w = Vector3::unit_vector(n);
+        let t = tangent - &(Vector3::dot(tangent, &w) * &w);
+        if t.near_zero() {
+            return Onb::new(n);
+        }
+
+        let u = Vector3::unit_vector(&t);
+        let v = Vector3::cross(&w, &u);
+
+        Self { axis: [u, v, w] }
+    }
+
+    pub fn w(&self) -> Vector3 {
+        self.axis[2]
+    }
+
+    pub fn to_basis(&self, v: &Vector3) -> Vector3 {
+        // Transform from local space to basis coordinates
+        Vector3::new(
+            Vector3::dot(v, &self.axis[0]),
+            Vector3::dot(v, &self.axis[1]),
+            Vector3::dot(v, &self.axis[2]),
+        )
+    }
+
+    pub fn transform(&self, v: &Vector3) -> Vector3 {
+        // Transform from basis coordinates to local space
+        &(&(v.x * &self.axis[0]) + &(v.y * &self.axis[1])) + &(v.z * &self.axis[2])
+    }
+}
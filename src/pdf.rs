@@ -0,0 +1,105 @@
+use std::f64::consts::PI;
+
+use crate::{onb::Onb, sampler, vector3::Vector3};
+
+pub trait Pdf {
+    // Probability density of sampling 'direction', with respect to solid angle
+    fn value(&self, direction: &Vector3) -> f64;
+
+    // Direction drawn with this density from the sample values 'uc' and 'u'
+    fn generate(&self, uc: f64, u: (f64, f64)) -> Vector3;
+}
+
+impl<P: Pdf + ?Sized> Pdf for Box<P> {
+    fn value(&self, direction: &Vector3) -> f64 {
+        (**self).value(direction)
+    }
+
+    fn generate(&self, uc: f64, u: (f64, f64)) -> Vector3 {
+        (**self).generate(uc, u)
+    }
+}
+
+// Multiple importance sampling weight for a sample drawn from 'f', combined with 'g'
+pub fn power_heuristic(f_pdf: f64, g_pdf: f64) -> f64 {
+    let f2 = f_pdf * f_pdf;
+    let g2 = g_pdf * g_pdf;
+    if f2 + g2 <= 0.0 {
+        return 0.0;
+    }
+    f2 / (f2 + g2)
+}
+
+pub struct CosinePdf {
+    uvw: Onb,
+}
+
+impl CosinePdf {
+    pub fn new(w: &Vector3) -> Self {
+        Self { uvw: Onb::new(w) }
+    }
+}
+
+impl Pdf for CosinePdf {
+    fn value(&self, direction: &Vector3) -> f64 {
+        let cosine_theta = Vector3::dot(&Vector3::unit_vector(direction), &self.uvw.w());
+        f64::max(0.0, cosine_theta / PI)
+    }
+
+    fn generate(&self, _uc: f64, u: (f64, f64)) -> Vector3 {
+        self.uvw.transform(&sampler::cosine_hemisphere(u))
+    }
+}
+
+#[derive(Default)]
+pub struct MixturePdf {
+    components: Vec<(f64, Box<dyn Pdf>)>,
+}
+
+impl MixturePdf {
+    pub fn new() -> Self {
+        Self { components: vec![] }
+    }
+
+    // Components are picked in proportion to their weights
+    pub fn add(&mut self, weight: f64, pdf: impl Pdf + 'static) {
+        if weight > 0.0 {
+            self.components.push((weight, Box::new(pdf)));
+        }
+    }
+
+    fn total_weight(&self) -> f64 {
+        self.components.iter().map(|(weight, _)| weight).sum()
+    }
+}
+
+impl Pdf for MixturePdf {
+    fn value(&self, direction: &Vector3) -> f64 {
+        let total = self.total_weight();
+        if total <= 0.0 {
+            return 0.0;
+        }
+
+        self.components
+            .iter()
+            .map(|(weight, pdf)| weight * pdf.value(direction))
+            .sum::<f64>()
+            / total
+    }
+
+    fn generate(&self, uc: f64, u: (f64, f64)) -> Vector3 {
+        // 'uc' picks the component, then is stretched back over [0, 1) for it to use
+        let mut pick = uc * self.total_weight();
+        for (weight, pdf) in self.components.iter() {
+            if pick < *weight {
+                return pdf.generate(f64::min(pick / weight, 1.0 - f64::EPSILON), u);
+            }
+            pick -= weight;
+        }
+
+        match self.components.last() {
+            Some((_, pdf)) => pdf.generate(uc, u),
+            None => Vector3::zero(),
+        }
+    }
+}
@@ -0,0 +1,291 @@
+use std::{f64::consts::PI, rc::Rc};
+
+use crate::{
+    color::Color,
+    hittable::HitRecord,
+    material::{Material, ScatterRecord},
+    microfacet::{MicrofacetDielectricPdf, MicrofacetReflectionPdf, TrowbridgeReitz},
+    onb::Onb,
+    pdf::{CosinePdf, MixturePdf},
+    ray::Ray,
+    texture::{SolidColor, Texture},
+    vector3::Vector3,
+};
+
+// Roughness is kept above this so every lobe stays a proper microfacet lobe
+const MIN_ROUGHNESS: f64 = 0.05;
+
+// A Disney-style principled BSDF (Burley 2012, 2015), layering a retro-reflective diffuse base
+// with sheen, a GGX specular lobe, a rough dielectric transmission lobe and a clearcoat
+pub struct Principled {
+    base_color: Rc<dyn Texture>,
+    metallic: Rc<dyn Texture>,
+    roughness: Rc<dyn Texture>,
+    specular: Rc<dyn Texture>,
+    specular_tint: Rc<dyn Texture>,
+    sheen: Rc<dyn Texture>,
+    clearcoat: Rc<dyn Texture>,
+    clearcoat_gloss: Rc<dyn Texture>,
+    transmission: Rc<dyn Texture>,
+    ior: Rc<dyn Texture>,
+}
+
+// Parameters evaluated at a hit point
+struct Parameters {
+    base_color: Color,
+    metallic: f64,
+    roughness: f64,
+    specular: f64,
+    specular_tint: f64,
+    sheen: f64,
+    clearcoat: f64,
+    clearcoat_gloss: f64,
+    transmission: f64,
+    ior: f64,
+}
+
+// Lobe weights derived from the parameters
+struct Lobes {
+    diffuse: f64,
+    specular: f64,
+    transmission: f64,
+    clearcoat: f64,
+}
+
+impl Principled {
+    pub fn new(base_color: impl Texture + 'static) -> Self {
+        Self {
+            base_color: Rc::new(base_color),
+            metallic: Rc::new(SolidColor::scalar(0.0)),
+            roughness: Rc::new(SolidColor::scalar(0.5)),
+            specular: Rc::new(SolidColor::scalar(0.5)),
+            specular_tint: Rc::new(SolidColor::scalar(0.0)),
+            sheen: Rc::new(SolidColor::scalar(0.0)),
+            clearcoat: Rc::new(SolidColor::scalar(0.0)),
+            clearcoat_gloss: Rc::new(SolidColor::scalar(1.0)),
+            transmission: Rc::new(SolidColor::scalar(0.0)),
+            ior: Rc::new(SolidColor::scalar(1.5)),
+        }
+    }
+
+    pub fn with_metallic(mut self, metallic: impl Texture + 'static) -> Self {
+        self.metallic = Rc::new(metallic);
+        self
+    }
+
+    pub fn with_roughness(mut self, roughness: impl Texture + 'static) -> Self {
+        self.roughness = Rc::new(roughness);
+        self
+    }
+
+    pub fn with_specular(mut self, specular: impl Texture + 'static) -> Self {
+        self.specular = Rc::new(specular);
+        self
+    }
+
+    pub fn with_specular_tint(mut self, specular_tint: impl Texture + 'static) -> Self {
+        self.specular_tint = Rc::new(specular_tint);
+        self
+    }
+
+    pub fn with_sheen(mut self, sheen: impl Texture + 'static) -> Self {
+        self.sheen = Rc::new(sheen);
+        self
+    }
+
+    pub fn with_clearcoat(mut self, clearcoat: impl Texture + 'static) -> Self {
+        self.clearcoat = Rc::new(clearcoat);
+        self
+    }
+
+    pub fn with_clearcoat_gloss(mut self, clearcoat_gloss: impl Texture + 'static) -> Self {
+        self.clearcoat_gloss = Rc::new(clearcoat_gloss);
+        self
+    }
+
+    pub fn with_transmission(mut self, transmission: impl Texture + 'static) -> Self {
+        self.transmission = Rc::new(transmission);
+        self
+    }
+
+    pub fn with_ior(mut self, ior: impl Texture + 'static) -> Self {
+        self.ior = Rc::new(ior);
+        self
+    }
+
+    fn parameters(&self, rec: &HitRecord) -> Parameters {
+        let scalar = |texture: &Rc<dyn Texture>| texture.value(rec.u, rec.v, &rec.p).luminance();
+
+        Parameters {
+            base_color: self.base_color.value(rec.u, rec.v, &rec.p),
+            metallic: scalar(&self.metallic).clamp(0.0, 1.0),
+            roughness: scalar(&self.roughness).clamp(MIN_ROUGHNESS, 1.0),
+            specular: scalar(&self.specular).max(0.0),
+            specular_tint: scalar(&self.specular_tint).clamp(0.0, 1.0),
+            sheen: scalar(&self.sheen).max(0.0),
+            clearcoat: scalar(&self.clearcoat).max(0.0),
+            clearcoat_gloss: scalar(&self.clearcoat_gloss).clamp(0.0, 1.0),
+            transmission: scalar(&self.transmission).clamp(0.0, 1.0),
+            ior: scalar(&self.ior).max(1.0),
+        }
+    }
+}
+
+impl Parameters {
+    fn lobes(&self) -> Lobes {
+        let dielectric = 1.0 - self.metallic;
+        Lobes {
+            diffuse: dielectric * (1.0 - self.transmission),
+            specular: 1.0 - dielectric * self.transmission,
+            transmission: dielectric * self.transmission,
+            clearcoat: 0.25 * self.clearcoat,
+        }
+    }
+
+    fn tint(&self) -> Color {
+        // Base color normalized to unit luminance, isolating hue and saturation
+        let luminance = self.base_color.luminance();
+        if luminance > 0.0 {
+            &self.base_color / luminance
+        } else {
+            Color::new(1.0, 1.0, 1.0)
+        }
+    }
+
+    fn specular_distribution(&self) -> TrowbridgeReitz {
+        TrowbridgeReitz::new(self.roughness, self.roughness)
+    }
+
+    fn clearcoat_distribution(&self) -> TrowbridgeReitz {
+        // Glossier clearcoats are smoother; the range follows Disney's 0.1 to 0.001 alpha
+        let roughness = 0.316 + (0.0316 - 0.316) * self.clearcoat_gloss;
+        TrowbridgeReitz::new(roughness, roughness)
+    }
+
+    fn eta(&self, rec: &HitRecord) -> f64 {
+        if rec.front_face {
+            self.ior
+        } else {
+            1.0 / self.ior
+        }
+    }
+}
+
+fn schlick_weight(cos_theta: f64) -> f64 {
+    f64::powi((1.0 - cos_theta).clamp(0.0, 1.0), 5)
+}
+
+fn lerp(a: &Color, b: &Color, t: f64) -> Color {
+    &((1.0 - t) * a) + &(t * b)
+}
+
+impl Material for Principled {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        _uc: f64,
+        _u: (f64, f64),
+    ) -> Option<ScatterRecord> {
+        let params = self.parameters(rec);
+        let lobes = params.lobes();
+        let wo = -r_in.direction();
+
+        // Sample the lobes roughly in proportion to their contribution
+        let mut pdf = MixturePdf::new();
+        pdf.add(lobes.diffuse, CosinePdf::new(&rec.normal));
+        pdf.add(
+            lobes.specular,
+            MicrofacetReflectionPdf::new(
+                Onb::new(&rec.normal),
+                &wo,
+                params.specular_distribution(),
+            ),
+        );
+        pdf.add(
+            lobes.transmission,
+            MicrofacetDielectricPdf::new(
+                Onb::new(&rec.normal),
+                &wo,
+                params.eta(rec),
+                params.specular_distribution(),
+            ),
+        );
+        pdf.add(
+            lobes.clearcoat,
+            MicrofacetReflectionPdf::new(
+                Onb::new(&rec.normal),
+                &wo,
+                params.clearcoat_distribution(),
+            ),
+        );
+
+        Some(ScatterRecord::diffuse(params.base_color, pdf))
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+        let params = self.parameters(rec);
+        let lobes = params.lobes();
+
+        let uvw = Onb::new(&rec.normal);
+        let wo = uvw.to_basis(&-Vector3::unit_vector(&r_in.direction()));
+        let wi = uvw.to_basis(&Vector3::unit_vector(&scattered.direction()));
+        if wo.z <= 0.0 {
+            return Color::zero();
+        }
+
+        let mut f_cos = Color::zero();
+
+        // Transmission through a rough dielectric interface, tinted by the base color
+        if lobes.transmission > 0.0 {
+            let distribution = params.specular_distribution();
+            let dielectric = distribution.eval_dielectric(&wo, &wi, params.eta(rec));
+            f_cos += &((lobes.transmission * dielectric) * &params.base_color);
+        }
+
+        // The remaining lobes only reflect
+        if wi.z <= 0.0 {
+            return f_cos;
+        }
+
+        let wh = Vector3::unit_vector(&(&wo + &wi));
+        let cos_d = Vector3::dot(&wi, &wh).clamp(0.0, 1.0);
+        let tint = params.tint();
+
+        if lobes.diffuse > 0.0 {
+            // Burley diffuse with grazing retro-reflection, plus sheen
+            let fd90 = 0.5 + 2.0 * params.roughness * cos_d * cos_d;
+            let fl = schlick_weight(wi.z);
+            let fv = schlick_weight(wo.z);
+            let diffuse = (1.0 + (fd90 - 1.0) * fl) * (1.0 + (fd90 - 1.0) * fv) / PI;
+
+            let sheen_color = lerp(&Color::new(1.0, 1.0, 1.0), &tint, 0.5);
+            let sheen = (params.sheen * schlick_weight(cos_d)) * &sheen_color;
+
+            let lobe = &(diffuse * &params.base_color) + &sheen;
+            f_cos += &((lobes.diffuse * wi.z) * &lobe);
+        }
+
+        if lobes.specular > 0.0 {
+            // Dielectric specular color blends towards the base color as metallic increases
+            let dielectric_f0 = (0.08 * params.specular)
+                * &lerp(&Color::new(1.0, 1.0, 1.0), &tint, params.specular_tint);
+            let f0 = lerp(&dielectric_f0, &params.base_color, params.metallic);
+            let fresnel = lerp(&f0, &Color::new(1.0, 1.0, 1.0), schlick_weight(cos_d));
+
+            let distribution = params.specular_distribution();
+            let dg = distribution.d(&wh) * distribution.g(&wo, &wi);
+            f_cos += &((lobes.specular * dg / (4.0 * wo.z)) * &fresnel);
+        }
+
+        if lobes.clearcoat > 0.0 {
+            let fresnel = 0.04 + 0.96 * schlick_weight(cos_d);
+            let distribution = params.clearcoat_distribution();
+            let dg = distribution.d(&wh) * distribution.g(&wo, &wi);
+            let clearcoat = lobes.clearcoat * fresnel * dg / (4.0 * wo.z);
+            f_cos += &Color::new(clearcoat, clearcoat, clearcoat);
+        }
+
+        f_cos
+    }
+}
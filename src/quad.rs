@@ -0,0 +1,100 @@
+use crate::{
+    hittable::{HitRecord, Hittable},
+    hittable_list::HittableList,
+    interval::Interval,
+    material::Material,
+    ray::Ray,
+    vector3::{Point3, Vector3},
+};
+use std::rc::Rc;
+
+pub struct Quad {
+    // Corner 'q' and the two edges leaving it
+    q: Point3,
+    u: Vector3,
+    v: Vector3,
+    w: Vector3,
+    mat: Rc<dyn Material>,
+    normal: Vector3,
+    d: f64,
+}
+
+impl Quad {
+    pub fn new(q: Point3, u: Vector3, v: Vector3, mat: Rc<dyn Material>) -> Self {
+        let n = Vector3::cross(&u, &v);
+        let normal = Vector3::unit_vector(&n);
+        let d = Vector3::dot(&normal, &q);
+        let w = &n / Vector3::dot(&n, &n);
+
+        Self {
+            q,
+            u,
+            v,
+            w,
+            mat,
+            normal,
+            d,
+        }
+    }
+}
+
+impl Hittable for Quad {
+    fn hit(&self, ray: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+        let denom = Vector3::dot(&self.normal, &ray.direction());
+
+        // No hit if the ray is parallel to the plane
+        if f64::abs(denom) < 1e-8 {
+            return false;
+        }
+
+        let t = (self.d - Vector3::dot(&self.normal, &ray.origin())) / denom;
+        if !ray_t.surronds(t) {
+            return false;
+        }
+
+        // Express the hit point in the plane's (u, v) coordinates, and keep it inside the quad
+        let intersection = ray.at(t);
+        let planar_hitpt = &intersection - &self.q;
+        let alpha = Vector3::dot(&self.w, &Vector3::cross(&planar_hitpt, &self.v));
+        let beta = Vector3::dot(&self.w, &Vector3::cross(&self.u, &planar_hitpt));
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+            return false;
+        }
+
+        rec.t = t;
+        rec.p = intersection;
+        rec.set_face_normal(ray, &self.normal);
+        (rec.u, rec.v) = (alpha, beta);
+        (rec.tangent, rec.bitangent) = (self.u, self.v);
+        rec.mat = self.mat.clone();
+
+        rec.is_opaque(ray)
+    }
+}
+
+// Returns the box with opposite corners 'a' and 'b', made of six quads
+pub fn make_box(a: &Point3, b: &Point3, mat: Rc<dyn Material>) -> HittableList {
+    let mut sides = HittableList::zero();
+
+    let min = Point3::new(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z));
+    let max = Point3::new(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z));
+
+    let dx = Vector3::new(max.x - min.x, 0.0, 0.0);
+    let dy = Vector3::new(0.0, max.y - min.y, 0.0);
+    let dz = Vector3::new(0.0, 0.0, max.z - min.z);
+
+    // Front, right, back, left, top and bottom
+    let corners = [
+        (Point3::new(min.x, min.y, max.z), dx, dy),
+        (Point3::new(max.x, min.y, max.z), -dz, dy),
+        (Point3::new(max.x, min.y, min.z), -dx, dy),
+        (Point3::new(min.x, min.y, min.z), dz, dy),
+        (Point3::new(min.x, max.y, max.z), dx, -dz),
+        (Point3::new(min.x, min.y, min.z), dx, dz),
+    ];
+    for (q, u, v) in corners {
+        sides.add(Quad::new(q, u, v, mat.clone()));
+    }
+
+    sides
+}
@@ -1,8 +1,14 @@
 use crate::vector3::{Point3, Vector3};
 
+#[derive(Clone, Copy)]
 pub struct Ray {
     origin: Point3,
     direction: Vector3,
+    // Wavelength in nanometers carried by spectral paths
+    wavelength: Option<f64>,
+    // Sample value for alpha testing the surfaces the ray meets, hashed with each hit point.
+    // Rays built without one share the same default
+    alpha_sample: f64,
 }
 
 impl Ray {
@@ -10,9 +16,21 @@ impl Ray {
         Self {
             origin: *origin,
             direction: *direction,
+            wavelength: None,
+            alpha_sample: 0.5,
         }
     }
 
+    pub fn with_wavelength(mut self, wavelength: Option<f64>) -> Self {
+        self.wavelength = wavelength;
+        self
+    }
+
+    pub fn with_alpha_sample(mut self, alpha_sample: f64) -> Self {
+        self.alpha_sample = alpha_sample;
+        self
+    }
+
     pub fn zero() -> Self {
         Ray::new(&Point3::zero(), &Vector3::zero())
     }
@@ -25,6 +43,14 @@ impl Ray {
         self.direction
     }
 
+    pub fn wavelength(&self) -> Option<f64> {
+        self.wavelength
+    }
+
+    pub fn alpha_sample(&self) -> f64 {
+        self.alpha_sample
+    }
+
     pub fn at(&self, t: f64) -> Point3 {
         &self.origin + &(t * &self.direction)
     }
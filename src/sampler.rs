@@ -0,0 +1,741 @@
+use std::f64::consts::PI;
+
+use crate::{utils, vector3::Vector3};
+
+// Largest f64 below one, so sample values stay in [0, 1)
+const ONE_MINUS_EPSILON: f64 = 1.0 - f64::EPSILON / 2.0;
+
+// Bases of the Halton sequence's dimensions
+const PRIMES: [u32; 32] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109, 113, 127, 131,
+];
+
+// Source of the values in [0, 1) that drive a camera sample. Every sample of a pixel consumes
+// its dimensions in the same order: the position in the pixel, the lens, the wavelength, then
+// for each bounce the alpha test, the BSDF, each light and the environment, the BSDF's
+// direction and Russian roulette, drawn whether the bounce uses them or not. Patterns that
+// spread the samples of a pixel evenly over each dimension then converge faster than
+// independent random numbers. Scenes have no motion blur, so no dimension is spent on time
+pub trait Sampler {
+    // Starts sample 'index' of the pixel at column i, row j, from its first dimension
+    fn start_pixel_sample(&mut self, i: i32, j: i32, index: u32);
+
+    fn get_1d(&mut self) -> f64;
+
+    fn get_2d(&mut self) -> (f64, f64);
+}
+
+#[derive(Clone, Copy)]
+pub enum Sampling {
+    // Uniform random numbers for every dimension
+    Independent,
+    // One jittered sample per stratum of the unit interval and square, with the strata
+    // shuffled independently for each dimension
+    Stratified,
+    // Halton sequence with Owen-scrambled digits, scrambled differently for each pixel
+    Halton,
+    // Owen-scrambled Sobol points, padded: pairs of dimensions are shuffled independently, so
+    // each stays well distributed however many are used
+    Sobol,
+    // Kensler's correlated multi-jittered pattern, stratified in 2D and along both axes
+    CorrelatedMultiJittered,
+    // Owen-scrambled Sobol points shared out over the image along a Z-order curve, with the
+    // order shuffled so that neighboring pixels get complementary samples. The error then
+    // shows up as fine blue noise instead of blotchy white noise, which cleans up previews
+    // with few samples per pixel
+    BlueNoise,
+}
+
+impl Sampling {
+    // A sampler for 'samples_per_pixel' samples over an image 'width' by 'height' pixels,
+    // randomized afresh for each render
+    pub fn sampler(&self, samples_per_pixel: u32, width: i32, height: i32) -> Box<dyn Sampler> {
+        let samples_per_pixel = samples_per_pixel.max(1);
+        let seed = rand::random();
+        match self {
+            Sampling::Independent => Box::new(IndependentSampler),
+            Sampling::Stratified => Box::new(StratifiedSampler::new(samples_per_pixel, seed)),
+            Sampling::Halton => Box::new(HaltonSampler::new(seed)),
+            Sampling::Sobol => Box::new(SobolSampler::new(samples_per_pixel, seed)),
+            Sampling::CorrelatedMultiJittered => Box::new(CmjSampler::new(samples_per_pixel, seed)),
+            Sampling::BlueNoise => Box::new(ZSobolSampler::new(
+                samples_per_pixel,
+                width.max(height) as u32,
+                seed,
+            )),
+        }
+    }
+}
+
+pub struct IndependentSampler;
+
+impl Sampler for IndependentSampler {
+    fn start_pixel_sample(&mut self, _i: i32, _j: i32, _index: u32) {}
+
+    fn get_1d(&mut self) -> f64 {
+        utils::canonical_random_number()
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        (
+            utils::canonical_random_number(),
+            utils::canonical_random_number(),
+        )
+    }
+}
+
+// Position in the sample stream shared by the deterministic samplers
+#[derive(Default)]
+struct PixelSample {
+    i: i32,
+    j: i32,
+    index: u32,
+    dimension: u32,
+}
+
+impl PixelSample {
+    fn start(&mut self, i: i32, j: i32, index: u32) {
+        *self = PixelSample {
+            i,
+            j,
+            index,
+            dimension: 0,
+        };
+    }
+
+    // Hash of the pixel and the current dimension, which then moves on by 'count'
+    fn next_hash(&mut self, seed: u64, count: u32) -> u64 {
+        let pixel = ((self.i as u32 as u64) << 32) | self.j as u32 as u64;
+        let hash = utils::mix_bits(
+            seed ^ utils::mix_bits(pixel ^ utils::mix_bits(self.dimension as u64 + 1)),
+        );
+        self.dimension += count;
+        hash
+    }
+}
+
+pub struct StratifiedSampler {
+    samples_per_pixel: u32,
+    // Strata along x and y of the 2D patterns, covering at least 'samples_per_pixel'
+    x_strata: u32,
+    y_strata: u32,
+    seed: u64,
+    state: PixelSample,
+}
+
+impl StratifiedSampler {
+    pub fn new(samples_per_pixel: u32, seed: u64) -> Self {
+        let (x_strata, y_strata) = grid(samples_per_pixel);
+        Self {
+            samples_per_pixel,
+            x_strata,
+            y_strata,
+            seed,
+            state: PixelSample::default(),
+        }
+    }
+}
+
+impl Sampler for StratifiedSampler {
+    fn start_pixel_sample(&mut self, i: i32, j: i32, index: u32) {
+        self.state.start(i, j, index);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let hash = self.state.next_hash(self.seed, 1);
+        let stratum = permutation_element(self.state.index, self.samples_per_pixel, hash as u32);
+        (stratum as f64 + utils::canonical_random_number()) / self.samples_per_pixel as f64
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        let hash = self.state.next_hash(self.seed, 2);
+        let stratum =
+            permutation_element(self.state.index, self.x_strata * self.y_strata, hash as u32);
+        let (x, y) = (stratum % self.x_strata, stratum / self.x_strata);
+        (
+            (x as f64 + utils::canonical_random_number()) / self.x_strata as f64,
+            (y as f64 + utils::canonical_random_number()) / self.y_strata as f64,
+        )
+    }
+}
+
+pub struct HaltonSampler {
+    seed: u64,
+    state: PixelSample,
+}
+
+impl HaltonSampler {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            state: PixelSample::default(),
+        }
+    }
+
+    fn sample_dimension(&self, dimension: u32, hash: u64) -> f64 {
+        // Past the table, dimensions reuse its bases with a different scramble
+        let base = PRIMES[dimension as usize % PRIMES.len()];
+        owen_scrambled_radical_inverse(base, self.state.index as u64, hash)
+    }
+}
+
+impl Sampler for HaltonSampler {
+    fn start_pixel_sample(&mut self, i: i32, j: i32, index: u32) {
+        self.state.start(i, j, index);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let dimension = self.state.dimension;
+        let hash = self.state.next_hash(self.seed, 1);
+        self.sample_dimension(dimension, hash)
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        let dimension = self.state.dimension;
+        let hash = self.state.next_hash(self.seed, 2);
+        (
+            self.sample_dimension(dimension, hash),
+            self.sample_dimension(dimension + 1, utils::mix_bits(hash)),
+        )
+    }
+}
+
+pub struct SobolSampler {
+    samples_per_pixel: u32,
+    seed: u64,
+    state: PixelSample,
+}
+
+impl SobolSampler {
+    pub fn new(samples_per_pixel: u32, seed: u64) -> Self {
+        Self {
+            samples_per_pixel,
+            seed,
+            state: PixelSample::default(),
+        }
+    }
+
+    fn shuffled_index(&self, hash: u64) -> u32 {
+        permutation_element(self.state.index, self.samples_per_pixel, hash as u32)
+    }
+}
+
+impl Sampler for SobolSampler {
+    fn start_pixel_sample(&mut self, i: i32, j: i32, index: u32) {
+        self.state.start(i, j, index);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let hash = self.state.next_hash(self.seed, 1);
+        let index = self.shuffled_index(hash);
+        to_unit(owen_scramble(sobol_first(index), (hash >> 32) as u32))
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        let hash = self.state.next_hash(self.seed, 2);
+        let index = self.shuffled_index(hash);
+        (
+            to_unit(owen_scramble(sobol_first(index), (hash >> 32) as u32)),
+            to_unit(owen_scramble(
+                sobol_second(index),
+                (utils::mix_bits(hash) >> 32) as u32,
+            )),
+        )
+    }
+}
+
+pub struct CmjSampler {
+    samples_per_pixel: u32,
+    x_strata: u32,
+    y_strata: u32,
+    seed: u64,
+    state: PixelSample,
+}
+
+impl CmjSampler {
+    pub fn new(samples_per_pixel: u32, seed: u64) -> Self {
+        let (x_strata, y_strata) = grid(samples_per_pixel);
+        Self {
+            samples_per_pixel,
+            x_strata,
+            y_strata,
+            seed,
+            state: PixelSample::default(),
+        }
+    }
+}
+
+impl Sampler for CmjSampler {
+    fn start_pixel_sample(&mut self, i: i32, j: i32, index: u32) {
+        self.state.start(i, j, index);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let p = self.state.next_hash(self.seed, 1) as u32;
+        let stratum = permutation_element(self.state.index, self.samples_per_pixel, p);
+        let jitter = randfloat(self.state.index, p.wrapping_mul(0x68bc21eb));
+        (stratum as f64 + jitter) / self.samples_per_pixel as f64
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        // Kensler, "Correlated Multi-Jittered Sampling" (2013)
+        let p = self.state.next_hash(self.seed, 2) as u32;
+        let (m, n) = (self.x_strata, self.y_strata);
+        let s = permutation_element(self.state.index, m * n, p.wrapping_mul(0x51633e2d));
+
+        // Samples in the same column share their shuffle across rows, and vice versa, which
+        // keeps both axes stratified into m * n intervals
+        let sx = permutation_element(s % m, m, p.wrapping_mul(0xa511e9b3));
+        let sy = permutation_element(s / m, n, p.wrapping_mul(0x63d83595));
+        let jx = randfloat(s, p.wrapping_mul(0xa399d265));
+        let jy = randfloat(s, p.wrapping_mul(0x711ad6a5));
+        (
+            ((s % m) as f64 + (sy as f64 + jx) / n as f64) / m as f64,
+            ((s / m) as f64 + (sx as f64 + jy) / m as f64) / n as f64,
+        )
+    }
+}
+
+// Ahmed and Wonka, "Screen-Space Blue-Noise Diffusion of Monte Carlo Sampling Error via
+// Hierarchical Ordering of Pixels" (2020), as pbrt-v4's ZSobolSampler
+pub struct ZSobolSampler {
+    log2_samples_per_pixel: u32,
+    // Base 4 digits in a sample's index along the Z-order curve
+    base4_digits: u32,
+    seed: u64,
+    morton_index: u64,
+    dimension: u32,
+}
+
+impl ZSobolSampler {
+    pub fn new(samples_per_pixel: u32, resolution: u32, seed: u64) -> Self {
+        let log2_samples_per_pixel = samples_per_pixel.next_power_of_two().ilog2();
+        let base4_digits =
+            resolution.max(1).next_power_of_two().ilog2() + log2_samples_per_pixel.div_ceil(2);
+        Self {
+            log2_samples_per_pixel,
+            base4_digits,
+            seed,
+            morton_index: 0,
+            dimension: 0,
+        }
+    }
+
+    fn sample_index(&self) -> u64 {
+        // Every ordering of a base 4 digit, to shuffle the four quadrants below each node of
+        // the Z-order quadtree
+        const PERMUTATIONS: [[u64; 4]; 24] = [
+            [0, 1, 2, 3],
+            [0, 1, 3, 2],
+            [0, 2, 1, 3],
+            [0, 2, 3, 1],
+            [0, 3, 2, 1],
+            [0, 3, 1, 2],
+            [1, 0, 2, 3],
+            [1, 0, 3, 2],
+            [1, 2, 0, 3],
+            [1, 2, 3, 0],
+            [1, 3, 2, 0],
+            [1, 3, 0, 2],
+            [2, 1, 0, 3],
+            [2, 1, 3, 0],
+            [2, 0, 1, 3],
+            [2, 0, 3, 1],
+            [2, 3, 0, 1],
+            [2, 3, 1, 0],
+            [3, 1, 2, 0],
+            [3, 1, 0, 2],
+            [3, 2, 1, 0],
+            [3, 2, 0, 1],
+            [3, 0, 2, 1],
+            [3, 0, 1, 2],
+        ];
+
+        // An odd power of two samples per pixel leaves a single base 2 digit at the bottom
+        let odd = self.log2_samples_per_pixel & 1 == 1;
+        let last_digit = if odd { 1 } else { 0 };
+        let dimension_hash = 0x55555555 * self.dimension as u64;
+
+        let mut index = 0;
+        for i in (last_digit..self.base4_digits).rev() {
+            let shift = 2 * i - last_digit;
+            let digit = (self.morton_index >> shift) & 3;
+            let higher_digits = self.morton_index >> (shift + 2);
+            let p = (utils::mix_bits(higher_digits ^ dimension_hash) >> 24) % 24;
+            index |= PERMUTATIONS[p as usize][digit as usize] << shift;
+        }
+        if odd {
+            let digit = self.morton_index & 1;
+            index |= digit ^ (utils::mix_bits((self.morton_index >> 1) ^ dimension_hash) & 1);
+        }
+
+        index
+    }
+
+    fn next_hash(&mut self, count: u32) -> u64 {
+        self.dimension += count;
+        utils::mix_bits(self.seed ^ utils::mix_bits(self.dimension as u64))
+    }
+}
+
+impl Sampler for ZSobolSampler {
+    fn start_pixel_sample(&mut self, i: i32, j: i32, index: u32) {
+        self.morton_index =
+            (encode_morton2(i as u32, j as u32) << self.log2_samples_per_pixel) | index as u64;
+        self.dimension = 0;
+    }
+
+    // The first two Sobol dimensions change no further past bit 32 of the index, which
+    // images up to 16384 pixels wide at 1024 samples per pixel stay below
+    fn get_1d(&mut self) -> f64 {
+        let index = self.sample_index() as u32;
+        let hash = self.next_hash(1);
+        to_unit(owen_scramble(sobol_first(index), hash as u32))
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        let index = self.sample_index() as u32;
+        let hash = self.next_hash(2);
+        (
+            to_unit(owen_scramble(sobol_first(index), hash as u32)),
+            to_unit(owen_scramble(sobol_second(index), (hash >> 32) as u32)),
+        )
+    }
+}
+
+// Point on the unit sphere, uniformly distributed
+pub fn uniform_sphere(u: (f64, f64)) -> Vector3 {
+    let z = 1.0 - 2.0 * u.0;
+    let r = f64::sqrt(f64::max(0.0, 1.0 - z * z));
+    let phi = 2.0 * PI * u.1;
+    Vector3::new(r * f64::cos(phi), r * f64::sin(phi), z)
+}
+
+// Point on the unit disk, uniformly distributed. Shirley and Chiu's concentric mapping keeps
+// nearby samples together, so it preserves the stratification of 'u'
+pub fn concentric_disk(u: (f64, f64)) -> Vector3 {
+    let (x, y) = (2.0 * u.0 - 1.0, 2.0 * u.1 - 1.0);
+    if x == 0.0 && y == 0.0 {
+        return Vector3::zero();
+    }
+
+    let (r, theta) = if x.abs() > y.abs() {
+        (x, PI / 4.0 * (y / x))
+    } else {
+        (y, PI / 2.0 - PI / 4.0 * (x / y))
+    };
+    Vector3::new(r * f64::cos(theta), r * f64::sin(theta), 0.0)
+}
+
+// Cosine-weighted direction around +z, from the disk projected up onto the hemisphere
+pub fn cosine_hemisphere(u: (f64, f64)) -> Vector3 {
+    let d = concentric_disk(u);
+    let z = f64::sqrt(f64::max(0.0, 1.0 - d.x * d.x - d.y * d.y));
+    Vector3::new(d.x, d.y, z)
+}
+
+fn grid(samples_per_pixel: u32) -> (u32, u32) {
+    // Closest to square grid of strata with room for every sample
+    let x = (samples_per_pixel as f64).sqrt().ceil() as u32;
+    (x, samples_per_pixel.div_ceil(x))
+}
+
+// Interleaves the bits of 'x' and 'y', which orders pixels along a Z-order curve
+fn encode_morton2(x: u32, y: u32) -> u64 {
+    fn spread(v: u32) -> u64 {
+        let mut v = v as u64;
+        v = (v | (v << 16)) & 0x0000ffff0000ffff;
+        v = (v | (v << 8)) & 0x00ff00ff00ff00ff;
+        v = (v | (v << 4)) & 0x0f0f0f0f0f0f0f0f;
+        v = (v | (v << 2)) & 0x3333333333333333;
+        v = (v | (v << 1)) & 0x5555555555555555;
+        v
+    }
+    (spread(y) << 1) | spread(x)
+}
+
+fn to_unit(v: u32) -> f64 {
+    v as f64 / 4294967296.0
+}
+
+// Element 'i' of a random permutation of 0..'l' chosen by 'p', without storing the permutation
+// (Kensler 2013)
+fn permutation_element(mut i: u32, l: u32, p: u32) -> u32 {
+    let mut w = l - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+    loop {
+        i ^= p;
+        i = i.wrapping_mul(0xe170893d);
+        i ^= p >> 16;
+        i ^= (i & w) >> 4;
+        i ^= p >> 8;
+        i = i.wrapping_mul(0x0929eb3f);
+        i ^= p >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | p >> 27);
+        i = i.wrapping_mul(0x6935fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dcb303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e501cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860a3df);
+        i &= w;
+        i ^= i >> 5;
+        if i < l {
+            break;
+        }
+    }
+    (i.wrapping_add(p)) % l
+}
+
+// Hashes 'i' to [0, 1), with 'p' choosing the hash
+fn randfloat(mut i: u32, p: u32) -> f64 {
+    i ^= p;
+    i ^= i >> 17;
+    i ^= i >> 10;
+    i = i.wrapping_mul(0xb36534e5);
+    i ^= i >> 12;
+    i ^= i >> 21;
+    i = i.wrapping_mul(0x93fc4795);
+    i ^= 0xdf6e307f;
+    i ^= i >> 17;
+    i = i.wrapping_mul(1 | p >> 18);
+    i as f64 / 4294967808.0
+}
+
+// Reverses the base 'base' digits of 'a' behind the radix point, shuffling each digit with a
+// permutation chosen by the digits before it
+fn owen_scrambled_radical_inverse(base: u32, mut a: u64, hash: u64) -> f64 {
+    let base64 = base as u64;
+    let inverse_base = 1.0 / base as f64;
+    let mut inverse_base_m = 1.0;
+    let mut reversed = 0u64;
+
+    // Stop once further digits fall below f64 precision
+    while 1.0 - (base as f64 - 1.0) * inverse_base_m < 1.0 {
+        let next = a / base64;
+        let digit = (a - next * base64) as u32;
+        let digit = permutation_element(digit, base, utils::mix_bits(hash ^ reversed) as u32);
+        reversed = reversed * base64 + digit as u64;
+        inverse_base_m *= inverse_base;
+        a = next;
+    }
+
+    f64::min(reversed as f64 * inverse_base_m, ONE_MINUS_EPSILON)
+}
+
+// Owen scrambling: flips each bit depending on a hash of the bits above it, which randomizes
+// the points while keeping their stratification (Burley 2020)
+fn owen_scramble(mut v: u32, seed: u32) -> u32 {
+    if seed & 1 != 0 {
+        v ^= 1 << 31;
+    }
+    for b in 1..32 {
+        let mask = !0u32 << (32 - b);
+        if (utils::mix_bits(((v & mask) ^ seed) as u64) as u32) & (1 << b) != 0 {
+            v ^= 1 << (31 - b);
+        }
+    }
+    v
+}
+
+// First two dimensions of the Sobol sequence, as 32-bit fractions
+fn sobol_first(index: u32) -> u32 {
+    index.reverse_bits()
+}
+
+fn sobol_second(mut index: u32) -> u32 {
+    let mut v = 1u32 << 31;
+    let mut result = 0;
+    while index != 0 {
+        if index & 1 != 0 {
+            result ^= v;
+        }
+        v ^= v >> 1;
+        index >>= 1;
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEEDS: [u64; 3] = [0, 1, 0x9e3779b97f4a7c15];
+
+    // Values of each 1D draw and points of each 2D draw, over the samples of a pixel
+    type Draws = (Vec<Vec<f64>>, Vec<Vec<(f64, f64)>>);
+
+    // Draws the first few dimensions of every sample of a pixel, alternating 1D and 2D
+    fn pixel_samples(sampler: &mut dyn Sampler, count: u32) -> Draws {
+        let (mut values, mut points) = (vec![vec![]; 3], vec![vec![]; 3]);
+        for index in 0..count {
+            sampler.start_pixel_sample(3, 5, index);
+            for dimension in 0..3 {
+                values[dimension].push(sampler.get_1d());
+                points[dimension].push(sampler.get_2d());
+            }
+        }
+        (values, points)
+    }
+
+    // Whether exactly one value lands in each of 'strata' equal intervals of [0, 1)
+    fn covers_strata(values: impl Iterator<Item = f64>, strata: usize) -> bool {
+        let mut hits = vec![0; strata];
+        for value in values {
+            assert!((0.0..1.0).contains(&value), "{value} is outside [0, 1)");
+            hits[(value * strata as f64) as usize] += 1;
+        }
+        hits.iter().all(|&hits| hits == 1)
+    }
+
+    // Whether exactly one point lands in each cell of an 'x' by 'y' grid over the unit square
+    fn covers_grid(points: &[(f64, f64)], x: usize, y: usize) -> bool {
+        let mut hits = vec![0; x * y];
+        for &(u, v) in points {
+            hits[(v * y as f64) as usize * x + (u * x as f64) as usize] += 1;
+        }
+        hits.iter().all(|&hits| hits == 1)
+    }
+
+    #[test]
+    fn stratified_samplers_cover_every_stratum() {
+        for seed in SEEDS {
+            let samplers: [Box<dyn Sampler>; 3] = [
+                Box::new(StratifiedSampler::new(16, seed)),
+                Box::new(SobolSampler::new(16, seed)),
+                Box::new(CmjSampler::new(16, seed)),
+            ];
+            for mut sampler in samplers {
+                let (values, points) = pixel_samples(sampler.as_mut(), 16);
+                for dimension in 0..3 {
+                    assert!(covers_strata(values[dimension].iter().copied(), 16));
+                    assert!(covers_grid(&points[dimension], 4, 4));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn sobol_and_cmj_points_are_stratified_along_both_axes() {
+        for seed in SEEDS {
+            let samplers: [Box<dyn Sampler>; 2] = [
+                Box::new(SobolSampler::new(16, seed)),
+                Box::new(CmjSampler::new(16, seed)),
+            ];
+            for mut sampler in samplers {
+                let (_, points) = pixel_samples(sampler.as_mut(), 16);
+                for points in points {
+                    assert!(covers_strata(points.iter().map(|p| p.0), 16));
+                    assert!(covers_strata(points.iter().map(|p| p.1), 16));
+                }
+            }
+        }
+
+        // Sobol points are a (0, 4, 2)-net, stratified over every elementary interval
+        let mut sampler = SobolSampler::new(16, 7);
+        let (_, points) = pixel_samples(&mut sampler, 16);
+        for points in points {
+            for (x, y) in [(1, 16), (2, 8), (4, 4), (8, 2), (16, 1)] {
+                assert!(covers_grid(&points, x, y), "{x} by {y}");
+            }
+        }
+    }
+
+    #[test]
+    fn halton_dimensions_are_stratified_in_their_base() {
+        // The 1D draw takes dimension 0, in base 2, and the 2D draw after it dimensions 1 and 2,
+        // in bases 3 and 5, so base^k samples cover the base^k strata of their dimension
+        for seed in SEEDS {
+            let mut sampler = HaltonSampler::new(seed);
+            let (values, _) = pixel_samples(&mut sampler, 16);
+            assert!(covers_strata(values[0].iter().copied(), 16));
+
+            let mut sampler = HaltonSampler::new(seed);
+            let (_, points) = pixel_samples(&mut sampler, 27);
+            assert!(covers_strata(points[0].iter().map(|p| p.0), 27));
+            assert!(covers_strata(points[0].iter().take(25).map(|p| p.1), 25));
+        }
+    }
+
+    #[test]
+    fn sobol_matches_the_known_sequence() {
+        let first = [0.0, 0.5, 0.25, 0.75, 0.125, 0.625, 0.375, 0.875];
+        let second = [0.0, 0.5, 0.75, 0.25, 0.625, 0.125, 0.375, 0.875];
+        for index in 0..8 {
+            assert_eq!(to_unit(sobol_first(index)), first[index as usize]);
+            assert_eq!(to_unit(sobol_second(index)), second[index as usize]);
+        }
+    }
+
+    #[test]
+    fn morton_codes_interleave_the_coordinates() {
+        assert_eq!(encode_morton2(0, 0), 0);
+        assert_eq!(encode_morton2(1, 0), 1);
+        assert_eq!(encode_morton2(0, 1), 2);
+        assert_eq!(encode_morton2(1, 1), 3);
+        assert_eq!(encode_morton2(2, 0), 4);
+        assert_eq!(encode_morton2(3, 5), 0b100111);
+        assert_eq!(encode_morton2(u32::MAX, 0), 0x5555555555555555);
+        assert_eq!(encode_morton2(0, u32::MAX), 0xaaaaaaaaaaaaaaaa);
+    }
+
+    #[test]
+    fn zsobol_indices_permute_the_image_pixel_by_pixel() {
+        // Each pixel gets a whole aligned block of Sobol indices, and the blocks of an image
+        // are a shuffle of all of them, including odd powers of two samples per pixel
+        for (samples_per_pixel, seed) in [(16, 0), (16, 3), (8, 5), (2, 11), (1, 7)] {
+            let mut sampler = ZSobolSampler::new(samples_per_pixel, 8, seed);
+            for dimension in [0, 1, 6] {
+                let mut blocks = vec![];
+                for j in 0..8 {
+                    for i in 0..8 {
+                        let mut indices = (0..samples_per_pixel)
+                            .map(|index| {
+                                sampler.start_pixel_sample(i, j, index);
+                                sampler.dimension = dimension;
+                                sampler.sample_index()
+                            })
+                            .collect::<Vec<u64>>();
+
+                        let block = indices[0] / samples_per_pixel as u64;
+                        indices.sort();
+                        let expected = (0..samples_per_pixel as u64)
+                            .map(|index| block * samples_per_pixel as u64 + index)
+                            .collect::<Vec<u64>>();
+                        assert_eq!(indices, expected);
+                        blocks.push(block);
+                    }
+                }
+
+                blocks.sort();
+                assert_eq!(blocks, (0..64).collect::<Vec<u64>>());
+            }
+        }
+    }
+
+    #[test]
+    fn zsobol_pixels_are_stratified() {
+        for seed in SEEDS {
+            let mut sampler = ZSobolSampler::new(16, 64, seed);
+            for (i, j) in [(0, 0), (3, 5), (63, 17)] {
+                let (mut values, mut points) = (vec![], vec![]);
+                for index in 0..16 {
+                    sampler.start_pixel_sample(i, j, index);
+                    values.push(sampler.get_1d());
+                    points.push(sampler.get_2d());
+                }
+                assert!(covers_strata(values.into_iter(), 16));
+                assert!(covers_grid(&points, 4, 4));
+                assert!(covers_strata(points.iter().map(|p| p.0), 16));
+                assert!(covers_strata(points.iter().map(|p| p.1), 16));
+            }
+        }
+    }
+}
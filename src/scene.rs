@@ -0,0 +1,33 @@
+use crate::{
+    color::Color,
+    environment::{Environment, Gradient},
+    hittable_list::HittableList,
+    light::Light,
+};
+
+pub struct Scene {
+    pub world: HittableList,
+    pub lights: Vec<Box<dyn Light>>,
+    pub environment: Box<dyn Environment>,
+}
+
+impl Scene {
+    pub fn new(world: HittableList) -> Self {
+        Self {
+            world,
+            lights: vec![],
+            environment: Box::new(Gradient::new(
+                Color::new(1.0, 1.0, 1.0),
+                Color::new(0.5, 0.7, 1.0),
+            )),
+        }
+    }
+
+    pub fn add_light(&mut self, light: impl Light + 'static) {
+        self.lights.push(Box::new(light))
+    }
+
+    pub fn set_environment(&mut self, environment: impl Environment + 'static) {
+        self.environment = Box::new(environment)
+    }
+}
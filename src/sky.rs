@@ -0,0 +1,166 @@
+use std::f64::consts::PI;
+
+use crate::{
+    color::Color, environment::Environment, light::DirectionalLight, utils, vector3::Vector3,
+};
+
+// Preetham, Shirley and Smits, "A Practical Analytic Model for Daylight" (1999)
+
+// The model's luminance is in kcd/m^2; this brings a clear noon zenith down to about 1
+const SKY_SCALE: f64 = 0.03;
+// Solar illuminance above the atmosphere, in klux, on the same scale as the sky
+const SUN_IRRADIANCE: f64 = 128.0 * SKY_SCALE;
+// Angular diameter of the sun as seen from the earth, in degrees
+const SUN_ANGULAR_DIAMETER: f64 = 0.53;
+
+struct Perez {
+    a: f64,
+    b: f64,
+    c: f64,
+    d: f64,
+    e: f64,
+}
+
+impl Perez {
+    fn f(&self, cos_theta: f64, gamma: f64) -> f64 {
+        (1.0 + self.a * f64::exp(self.b / cos_theta))
+            * (1.0 + self.c * f64::exp(self.d * gamma) + self.e * f64::cos(gamma).powi(2))
+    }
+}
+
+pub struct Sky {
+    sun_direction: Vector3,
+    turbidity: f64,
+    intensity: f64,
+    // Perez distributions and zenith values for luminance Y and chromaticities x, y
+    perez: [Perez; 3],
+    zenith: [f64; 3],
+    // Denominators of the model, F(0, theta_s), for each distribution
+    normalization: [f64; 3],
+}
+
+impl Sky {
+    // Elevation is measured in degrees above the horizon, azimuth in degrees from -z towards +x.
+    // Turbidity ranges from about 2 (very clear) to 10 (hazy)
+    pub fn new(sun_elevation: f64, sun_azimuth: f64, turbidity: f64, intensity: f64) -> Self {
+        let t = turbidity.clamp(1.7, 10.0);
+        let elevation = utils::degrees_to_radians(sun_elevation.clamp(0.0, 90.0));
+        let azimuth = utils::degrees_to_radians(sun_azimuth);
+
+        let sun_direction = Vector3::new(
+            f64::cos(elevation) * f64::sin(azimuth),
+            f64::sin(elevation),
+            -f64::cos(elevation) * f64::cos(azimuth),
+        );
+        let theta_s = PI / 2.0 - elevation;
+
+        let perez = [
+            Perez {
+                a: 0.1787 * t - 1.4630,
+                b: -0.3554 * t + 0.4275,
+                c: -0.0227 * t + 5.3251,
+                d: 0.1206 * t - 2.5771,
+                e: -0.0670 * t + 0.3703,
+            },
+            Perez {
+                a: -0.0193 * t - 0.2592,
+                b: -0.0665 * t + 0.0008,
+                c: -0.0004 * t + 0.2125,
+                d: -0.0641 * t - 0.8989,
+                e: -0.0033 * t + 0.0452,
+            },
+            Perez {
+                a: -0.0167 * t - 0.2608,
+                b: -0.0950 * t + 0.0092,
+                c: -0.0079 * t + 0.2102,
+                d: -0.0441 * t - 1.6537,
+                e: -0.0109 * t + 0.0529,
+            },
+        ];
+
+        let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * theta_s);
+        let zenith_luminance = (4.0453 * t - 4.9710) * f64::tan(chi) - 0.2155 * t + 2.4192;
+
+        let theta = [theta_s.powi(3), theta_s.powi(2), theta_s, 1.0];
+        let polynomial = |coefficients: [[f64; 4]; 3]| {
+            let row = |c: [f64; 4]| c.iter().zip(theta.iter()).map(|(c, t)| c * t).sum::<f64>();
+            t * t * row(coefficients[0]) + t * row(coefficients[1]) + row(coefficients[2])
+        };
+        let zenith_x = polynomial([
+            [0.00166, -0.00375, 0.00209, 0.0],
+            [-0.02903, 0.06377, -0.03202, 0.00394],
+            [0.11693, -0.21196, 0.06052, 0.25886],
+        ]);
+        let zenith_y = polynomial([
+            [0.00275, -0.00610, 0.00317, 0.0],
+            [-0.04214, 0.08970, -0.04153, 0.00516],
+            [0.15346, -0.26756, 0.06670, 0.26688],
+        ]);
+
+        let normalization = [
+            perez[0].f(1.0, theta_s),
+            perez[1].f(1.0, theta_s),
+            perez[2].f(1.0, theta_s),
+        ];
+
+        Self {
+            sun_direction,
+            turbidity: t,
+            intensity,
+            perez,
+            zenith: [zenith_luminance.max(0.0), zenith_x, zenith_y],
+            normalization,
+        }
+    }
+
+    // A directional light matching the sky's sun, including the disk's angular size
+    pub fn sun_light(&self) -> DirectionalLight {
+        DirectionalLight::new(
+            -self.sun_direction,
+            self.intensity * SUN_IRRADIANCE * &self.sun_transmittance(),
+            SUN_ANGULAR_DIAMETER,
+        )
+    }
+
+    fn sun_transmittance(&self) -> Color {
+        // Rayleigh and aerosol extinction along the sun's path through the atmosphere,
+        // evaluated at representative red, green and blue wavelengths (in micrometers)
+        let theta_s = f64::acos(self.sun_direction.y.clamp(0.0, 1.0));
+        let theta_degrees = theta_s.to_degrees();
+        let air_mass = 1.0 / (f64::cos(theta_s) + 0.15 * f64::powf(93.885 - theta_degrees, -1.253));
+
+        let beta = 0.04608 * self.turbidity - 0.04586;
+        let transmittance = |lambda: f64| {
+            let rayleigh = f64::exp(-0.008735 * f64::powf(lambda, -4.08) * air_mass);
+            let aerosol = f64::exp(-beta * f64::powf(lambda, -1.3) * air_mass);
+            rayleigh * aerosol
+        };
+
+        Color::new(
+            transmittance(0.68),
+            transmittance(0.55),
+            transmittance(0.44),
+        )
+    }
+}
+
+impl Environment for Sky {
+    fn radiance(&self, direction: &Vector3) -> Color {
+        // The model is only defined above the horizon; directions below reuse the horizon
+        let d = Vector3::unit_vector(direction);
+        let cos_theta = d.y.max(0.001);
+        let cos_gamma = Vector3::dot(&d, &self.sun_direction).clamp(-1.0, 1.0);
+        let gamma = f64::acos(cos_gamma);
+
+        let [luminance, x, y] = [0, 1, 2]
+            .map(|i| self.zenith[i] * self.perez[i].f(cos_theta, gamma) / self.normalization[i]);
+        if y <= 0.0 {
+            return Color::zero();
+        }
+
+        // xyY to CIE XYZ, then to linear sRGB
+        let rgb = Color::from_xyz(x * luminance / y, luminance, (1.0 - x - y) * luminance / y);
+
+        (self.intensity * SKY_SCALE) * &Color::new(rgb.x.max(0.0), rgb.y.max(0.0), rgb.z.max(0.0))
+    }
+}
@@ -0,0 +1,122 @@
+use crate::color::Color;
+
+// Visible range used for sampling wavelengths, in nanometers
+pub const LAMBDA_MIN: f64 = 380.0;
+pub const LAMBDA_MAX: f64 = 720.0;
+
+// Wavelength of the sodium D line, where a material's nominal index of refraction is quoted
+pub const LAMBDA_D: f64 = 587.6;
+
+pub fn sample_wavelength(u: f64) -> f64 {
+    LAMBDA_MIN + u * (LAMBDA_MAX - LAMBDA_MIN)
+}
+
+pub fn wavelength_pdf() -> f64 {
+    1.0 / (LAMBDA_MAX - LAMBDA_MIN)
+}
+
+fn piecewise_gaussian(lambda: f64, mu: f64, sigma_low: f64, sigma_high: f64) -> f64 {
+    let sigma = if lambda < mu { sigma_low } else { sigma_high };
+    let t = (lambda - mu) / sigma;
+    f64::exp(-0.5 * t * t)
+}
+
+// CIE 1931 color matching functions, using the multi-lobe fit of Wyman, Sloan and Shirley (2013)
+pub fn cie_xyz(lambda: f64) -> (f64, f64, f64) {
+    let x = 1.056 * piecewise_gaussian(lambda, 599.8, 37.9, 31.0)
+        + 0.362 * piecewise_gaussian(lambda, 442.0, 16.0, 26.7)
+        - 0.065 * piecewise_gaussian(lambda, 501.1, 20.4, 26.2);
+    let y = 0.821 * piecewise_gaussian(lambda, 568.8, 46.9, 40.5)
+        + 0.286 * piecewise_gaussian(lambda, 530.9, 16.3, 31.1);
+    let z = 1.217 * piecewise_gaussian(lambda, 437.0, 11.8, 36.0)
+        + 0.681 * piecewise_gaussian(lambda, 459.0, 26.0, 13.8);
+
+    (x, y, z)
+}
+
+// Upsamples an RGB value to a smooth spectrum and evaluates it at 'lambda'. The spectrum is a
+// blend of three overlapping bands that sum to one, so white stays flat and reflectances in
+// [0, 1] stay in [0, 1]
+pub fn rgb_to_spectrum(color: &Color, lambda: f64) -> f64 {
+    let blue = 1.0 / (1.0 + f64::exp((lambda - 490.0) / 10.0));
+    let red = 1.0 / (1.0 + f64::exp(-(lambda - 585.0) / 10.0));
+    let green = 1.0 - blue - red;
+
+    color.x * red + color.y * green + color.z * blue
+}
+
+// Evaluates 'color' at the path's wavelength, if it has one. Spectral paths carry their single
+// value in all three channels
+pub fn at_wavelength(color: &Color, wavelength: Option<f64>) -> Color {
+    match wavelength {
+        Some(lambda) => {
+            let value = rgb_to_spectrum(color, lambda);
+            Color::new(value, value, value)
+        }
+        None => *color,
+    }
+}
+
+// Linear sRGB of a flat, unit spectrum over the sampled range; spectral results are divided by
+// this so that it maps back to white
+pub fn flat_spectrum_rgb() -> Color {
+    let steps = 340;
+    let step = (LAMBDA_MAX - LAMBDA_MIN) / steps as f64;
+    let (mut x, mut y, mut z) = (0.0, 0.0, 0.0);
+
+    for i in 0..steps {
+        let (cx, cy, cz) = cie_xyz(LAMBDA_MIN + (i as f64 + 0.5) * step);
+        x += cx * step;
+        y += cy * step;
+        z += cz * step;
+    }
+
+    Color::from_xyz(x, y, z)
+}
+
+// Converts one radiance sample at 'lambda', drawn with density 'pdf', to linear sRGB
+pub fn sample_to_rgb(radiance: f64, lambda: f64, pdf: f64, white: &Color) -> Color {
+    let (x, y, z) = cie_xyz(lambda);
+    let scale = radiance / pdf;
+    let rgb = Color::from_xyz(x * scale, y * scale, z * scale);
+
+    Color::new(rgb.x / white.x, rgb.y / white.y, rgb.z / white.z)
+}
+
+#[derive(Clone, Copy)]
+pub enum Dispersion {
+    // n = a + b / lambda^2, with lambda in micrometers
+    Cauchy { a: f64, b: f64 },
+    // n^2 = 1 + sum(b_i lambda^2 / (lambda^2 - c_i)), with lambda in micrometers
+    Sellmeier { b: [f64; 3], c: [f64; 3] },
+}
+
+impl Dispersion {
+    pub fn bk7() -> Self {
+        Dispersion::Sellmeier {
+            b: [1.03961212, 0.231792344, 1.01046945],
+            c: [0.00600069867, 0.0200179144, 103.560653],
+        }
+    }
+
+    pub fn diamond() -> Self {
+        Dispersion::Sellmeier {
+            b: [0.3306, 4.3356, 0.0],
+            c: [0.030625, 0.011236, 0.0],
+        }
+    }
+
+    // Index of refraction at 'lambda', in nanometers
+    pub fn refraction_index(&self, lambda: f64) -> f64 {
+        let micrometers = lambda / 1000.0;
+        let l2 = micrometers * micrometers;
+
+        match self {
+            Dispersion::Cauchy { a, b } => a + b / l2,
+            Dispersion::Sellmeier { b, c } => {
+                let sum: f64 = b.iter().zip(c.iter()).map(|(b, c)| b * l2 / (l2 - c)).sum();
+                f64::sqrt(1.0 + sum)
+            }
+        }
+    }
+}
@@ -5,7 +5,7 @@ use crate::{
     ray::Ray,
     vector3::{Point3, Vector3},
 };
-use std::rc::Rc;
+use std::{f64::consts::PI, rc::Rc};
 
 pub struct Sphere {
     center: Point3,
@@ -21,6 +21,33 @@ impl Sphere {
             mat,
         }
     }
+
+    fn get_sphere_uv(p: &Point3) -> (f64, f64) {
+        // p: a given point on the sphere of radius one, centered at the origin
+        // u: returned value [0,1] of angle around the Y axis from X=-1
+        // v: returned value [0,1] of angle from Y=-1 to Y=+1
+        let theta = f64::acos(-p.y);
+        let phi = f64::atan2(-p.z, p.x) + std::f64::consts::PI;
+
+        (phi / (2.0 * PI), theta / PI)
+    }
+
+    fn get_sphere_tangents(&self, n: &Vector3) -> (Vector3, Vector3) {
+        // n: the outward unit normal at the hit point
+        // Returns the derivatives of the hit point along u (around Y) and v (towards Y=+1)
+        let (x, y, z) = (n.x, n.y, n.z);
+        let sin_theta = f64::sqrt(x * x + z * z);
+
+        let tangent = (2.0 * PI * self.radius) * &Vector3::new(z, 0.0, -x);
+        let bitangent = if sin_theta > 1e-8 {
+            (PI * self.radius) * &Vector3::new(-x * y / sin_theta, sin_theta, -y * z / sin_theta)
+        } else {
+            // At the poles any direction in the tangent plane will do
+            (PI * self.radius) * &Vector3::new(1.0, 0.0, 0.0)
+        };
+
+        (tangent, bitangent)
+    }
 }
 
 impl Hittable for Sphere {
@@ -38,22 +65,26 @@ impl Hittable for Sphere {
 
         let sqrtd = discriminant.sqrt();
 
-        // Find the nearest root that lies in the acceptable range
-        let mut root = (h - sqrtd) / a;
-        if !ray_t.surronds(root) {
-            root = (h + sqrtd) / a;
-
+        // Find the nearest root that lies in the acceptable range; if the surface is cut out
+        // there, the ray may still hit the far side
+        for root in [(h - sqrtd) / a, (h + sqrtd) / a] {
             if !ray_t.surronds(root) {
-                return false;
+                continue;
             }
-        }
 
-        rec.t = root;
-        rec.p = ray.at(rec.t);
-        let outward_normal = &(&rec.p - &self.center) / self.radius;
-        rec.set_face_normal(ray, &outward_normal);
-        rec.mat = self.mat.clone();
+            rec.t = root;
+            rec.p = ray.at(rec.t);
+            let outward_normal = &(&rec.p - &self.center) / self.radius;
+            rec.set_face_normal(ray, &outward_normal);
+            (rec.u, rec.v) = Sphere::get_sphere_uv(&outward_normal);
+            (rec.tangent, rec.bitangent) = self.get_sphere_tangents(&outward_normal);
+            rec.mat = self.mat.clone();
+
+            if rec.is_opaque(ray) {
+                return true;
+            }
+        }
 
-        true
+        false
     }
 }
@@ -0,0 +1,223 @@
+use std::{
+    f64::consts::PI,
+    ops::{Add, Div, Mul, Sub},
+};
+
+use crate::{color::Color, spectrum};
+
+// Number of wavelengths the reflectance is integrated over for RGB paths
+const RGB_STEPS: usize = 40;
+
+#[derive(Clone, Copy)]
+struct Complex {
+    re: f64,
+    im: f64,
+}
+
+impl Complex {
+    fn new(re: f64, im: f64) -> Self {
+        Self { re, im }
+    }
+
+    fn real(re: f64) -> Self {
+        Complex::new(re, 0.0)
+    }
+
+    fn norm_squared(self) -> f64 {
+        self.re * self.re + self.im * self.im
+    }
+
+    // Principal square root, so evanescent waves decay rather than grow
+    fn sqrt(self) -> Self {
+        let r = f64::sqrt(self.norm_squared()).sqrt();
+        let theta = 0.5 * f64::atan2(self.im, self.re);
+        Complex::new(r * f64::cos(theta), r * f64::sin(theta))
+    }
+
+    // e^(i z)
+    fn exp_i(self) -> Self {
+        let magnitude = f64::exp(-self.im);
+        Complex::new(magnitude * f64::cos(self.re), magnitude * f64::sin(self.re))
+    }
+}
+
+impl Add for Complex {
+    type Output = Complex;
+
+    fn add(self, rhs: Complex) -> Complex {
+        Complex::new(self.re + rhs.re, self.im + rhs.im)
+    }
+}
+
+impl Sub for Complex {
+    type Output = Complex;
+
+    fn sub(self, rhs: Complex) -> Complex {
+        Complex::new(self.re - rhs.re, self.im - rhs.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Complex;
+
+    fn mul(self, rhs: Complex) -> Complex {
+        Complex::new(
+            self.re * rhs.re - self.im * rhs.im,
+            self.re * rhs.im + self.im * rhs.re,
+        )
+    }
+}
+
+impl Div for Complex {
+    type Output = Complex;
+
+    fn div(self, rhs: Complex) -> Complex {
+        let denominator = rhs.norm_squared();
+        Complex::new(
+            (self.re * rhs.re + self.im * rhs.im) / denominator,
+            (self.im * rhs.re - self.re * rhs.im) / denominator,
+        )
+    }
+}
+
+// Amplitude reflection coefficients (s, p) between media 'n_i' and 'n_t', given the cosines of
+// the angles in each
+fn fresnel_amplitudes(n_i: Complex, cos_i: Complex, n_t: Complex, cos_t: Complex) -> [Complex; 2] {
+    let s = (n_i * cos_i - n_t * cos_t) / (n_i * cos_i + n_t * cos_t);
+    let p = (n_t * cos_i - n_i * cos_t) / (n_t * cos_i + n_i * cos_t);
+    [s, p]
+}
+
+// A thin transparent layer, such as a soap film, an anti-reflective coating or an oxide, whose
+// reflections interfere with the ones from the surface underneath
+#[derive(Clone, Copy)]
+pub struct ThinFilm {
+    // In nanometers
+    thickness: f64,
+    refraction_index: f64,
+}
+
+impl ThinFilm {
+    pub fn new(thickness: f64, refraction_index: f64) -> Self {
+        Self {
+            thickness: thickness.max(0.0),
+            refraction_index,
+        }
+    }
+
+    // Reflectance of the film on a dielectric, going from index 'n_incident' into 'n_transmitted'
+    pub fn dielectric_reflectance(
+        &self,
+        cos_theta_i: f64,
+        n_incident: f64,
+        n_transmitted: f64,
+        wavelength: Option<f64>,
+    ) -> Color {
+        self.reflectance(
+            cos_theta_i,
+            n_incident,
+            |_| Complex::real(n_transmitted),
+            wavelength,
+        )
+    }
+
+    // Reflectance of the film on a conductor with complex index 'eta' + i 'k', seen from air
+    pub fn conductor_reflectance(
+        &self,
+        cos_theta_i: f64,
+        eta: &Color,
+        k: &Color,
+        wavelength: Option<f64>,
+    ) -> Color {
+        self.reflectance(
+            cos_theta_i,
+            1.0,
+            |lambda| {
+                Complex::new(
+                    spectrum::rgb_to_spectrum(eta, lambda),
+                    spectrum::rgb_to_spectrum(k, lambda),
+                )
+            },
+            wavelength,
+        )
+    }
+
+    // Spectral paths evaluate the interference at their own wavelength; RGB paths integrate it
+    // against the color matching functions, so the film shows its actual interference colors
+    fn reflectance(
+        &self,
+        cos_theta_i: f64,
+        n_incident: f64,
+        substrate: impl Fn(f64) -> Complex,
+        wavelength: Option<f64>,
+    ) -> Color {
+        if let Some(lambda) = wavelength {
+            let r = self.reflectance_at(cos_theta_i, n_incident, substrate(lambda), lambda);
+            return Color::new(r, r, r);
+        }
+
+        let step = (spectrum::LAMBDA_MAX - spectrum::LAMBDA_MIN) / RGB_STEPS as f64;
+        let (mut x, mut y, mut z) = (0.0, 0.0, 0.0);
+        let (mut white_x, mut white_y, mut white_z) = (0.0, 0.0, 0.0);
+
+        for i in 0..RGB_STEPS {
+            let lambda = spectrum::LAMBDA_MIN + (i as f64 + 0.5) * step;
+            let r = self.reflectance_at(cos_theta_i, n_incident, substrate(lambda), lambda);
+            let (cx, cy, cz) = spectrum::cie_xyz(lambda);
+
+            x += r * cx;
+            y += r * cy;
+            z += r * cz;
+            white_x += cx;
+            white_y += cy;
+            white_z += cz;
+        }
+
+        let rgb = Color::from_xyz(x, y, z);
+        let white = Color::from_xyz(white_x, white_y, white_z);
+        Color::new(
+            (rgb.x / white.x).clamp(0.0, 1.0),
+            (rgb.y / white.y).clamp(0.0, 1.0),
+            (rgb.z / white.z).clamp(0.0, 1.0),
+        )
+    }
+
+    // Airy summation of the reflections off the top and bottom of the film, averaged over both
+    // polarizations
+    fn reflectance_at(
+        &self,
+        cos_theta_i: f64,
+        n_incident: f64,
+        substrate: Complex,
+        lambda: f64,
+    ) -> f64 {
+        let cos_theta_i = cos_theta_i.clamp(0.0, 1.0);
+        let n1 = Complex::real(n_incident);
+        let n2 = Complex::real(self.refraction_index);
+        let n3 = substrate;
+
+        // Snell's law, n sin(theta) is the same in every layer
+        let sin2_i = Complex::real(1.0 - cos_theta_i * cos_theta_i);
+        let cos_in = |n: Complex| {
+            let ratio = n1 / n;
+            (Complex::real(1.0) - ratio * ratio * sin2_i).sqrt()
+        };
+        let cos1 = Complex::real(cos_theta_i);
+        let cos2 = cos_in(n2);
+        let cos3 = cos_in(n3);
+
+        let r12 = fresnel_amplitudes(n1, cos1, n2, cos2);
+        let r23 = fresnel_amplitudes(n2, cos2, n3, cos3);
+
+        // Phase difference of a round trip through the film
+        let delta = Complex::real(4.0 * PI * self.thickness / lambda) * n2 * cos2;
+        let phase = delta.exp_i();
+
+        let reflectance = |r12: Complex, r23: Complex| {
+            let r = (r12 + r23 * phase) / (Complex::real(1.0) + r12 * r23 * phase);
+            r.norm_squared()
+        };
+
+        (0.5 * (reflectance(r12[0], r23[0]) + reflectance(r12[1], r23[1]))).clamp(0.0, 1.0)
+    }
+}
@@ -0,0 +1,101 @@
+use crate::{
+    hittable::{HitRecord, Hittable},
+    interval::Interval,
+    material::Material,
+    ray::Ray,
+    vector3::{Point3, Vector3},
+};
+use std::rc::Rc;
+
+pub struct Triangle {
+    vertices: [Point3; 3],
+    // Texture coordinates at each vertex
+    uvs: [(f64, f64); 3],
+    mat: Rc<dyn Material>,
+    normal: Vector3,
+    tangent: Vector3,
+    bitangent: Vector3,
+}
+
+impl Triangle {
+    pub fn new(a: Point3, b: Point3, c: Point3, mat: Rc<dyn Material>) -> Self {
+        let normal = Vector3::unit_vector(&Vector3::cross(&(&b - &a), &(&c - &a)));
+
+        Self {
+            vertices: [a, b, c],
+            uvs: [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)],
+            mat,
+            normal,
+            tangent: &b - &a,
+            bitangent: &c - &a,
+        }
+    }
+
+    pub fn with_uvs(mut self, uvs: [(f64, f64); 3]) -> Self {
+        self.uvs = uvs;
+
+        // Solve for the derivatives of the position along u and v from the triangle's edges
+        let [p0, p1, p2] = self.vertices;
+        let [(u0, v0), (u1, v1), (u2, v2)] = uvs;
+        let (du02, dv02) = (u0 - u2, v0 - v2);
+        let (du12, dv12) = (u1 - u2, v1 - v2);
+        let dp02 = &p0 - &p2;
+        let dp12 = &p1 - &p2;
+
+        let determinant = du02 * dv12 - dv02 * du12;
+        if f64::abs(determinant) > 1e-12 {
+            self.tangent = &(&(dv12 * &dp02) - &(dv02 * &dp12)) / determinant;
+            self.bitangent = &(&(du02 * &dp12) - &(du12 * &dp02)) / determinant;
+        }
+
+        self
+    }
+}
+
+impl Hittable for Triangle {
+    fn hit(&self, ray: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+        // Moller-Trumbore: solve for the barycentric coordinates and distance at once
+        let [p0, p1, p2] = self.vertices;
+        let edge1 = &p1 - &p0;
+        let edge2 = &p2 - &p0;
+
+        let pvec = Vector3::cross(&ray.direction(), &edge2);
+        let determinant = Vector3::dot(&edge1, &pvec);
+
+        // No hit if the ray is parallel to the triangle
+        if f64::abs(determinant) < 1e-12 {
+            return false;
+        }
+        let inv_determinant = 1.0 / determinant;
+
+        let tvec = &ray.origin() - &p0;
+        let b1 = Vector3::dot(&tvec, &pvec) * inv_determinant;
+        if !(0.0..=1.0).contains(&b1) {
+            return false;
+        }
+
+        let qvec = Vector3::cross(&tvec, &edge1);
+        let b2 = Vector3::dot(&ray.direction(), &qvec) * inv_determinant;
+        if b2 < 0.0 || b1 + b2 > 1.0 {
+            return false;
+        }
+
+        let t = Vector3::dot(&edge2, &qvec) * inv_determinant;
+        if !ray_t.surronds(t) {
+            return false;
+        }
+
+        let b0 = 1.0 - b1 - b2;
+        let [(u0, v0), (u1, v1), (u2, v2)] = self.uvs;
+
+        rec.t = t;
+        rec.p = ray.at(t);
+        rec.set_face_normal(ray, &self.normal);
+        rec.u = b0 * u0 + b1 * u1 + b2 * u2;
+        rec.v = b0 * v0 + b1 * v1 + b2 * v2;
+        (rec.tangent, rec.bitangent) = (self.tangent, self.bitangent);
+        rec.mat = self.mat.clone();
+
+        rec.is_opaque(ray)
+    }
+}
@@ -95,7 +95,7 @@ impl Vector3 {
 
     pub fn random_on_hemisphere(normal: &Vector3) -> Vector3 {
         let on_unit_sphere = Vector3::random_unit_vector();
-        if Vector3::dot(&on_unit_sphere, &normal) > 0.0 {
+        if Vector3::dot(&on_unit_sphere, normal) > 0.0 {
             return on_unit_sphere;
         }
         -on_unit_sphere